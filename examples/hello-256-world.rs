@@ -17,7 +17,7 @@ fn main() {
     loop {
         rustbox.present();
         match rustbox.poll_event(false) {
-            Ok(rustbox::Event::KeyEvent(key)) => {
+            Ok(rustbox::Event::KeyEvent(key, _)) => {
                 match key {
                     Key::Char('q') => { break; }
                     _ => { }
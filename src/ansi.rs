@@ -0,0 +1,260 @@
+//! Turning text with ANSI escape sequences, such as the output of `git diff --color` or a
+//! compiler, into spans.
+//!
+//! SGR sequences (`ESC [ ... m`) set the style and colors of the text after them. Colors come
+//! out as they were given, 16 color codes as the named colors and `Color::Byte`, 256 color and
+//! 24-bit ones as `Color::Byte` and `Color::Rgb`, and are mapped onto the current `OutputMode`
+//! when drawn, see `Color::nearest`. Other escape sequences, such as cursor movement or
+//! hyperlinks, are left out, as are C0 and C1 control characters other than tabs, which are
+//! expanded to the next multiple of 8 columns.
+//!
+//! ```
+//! use rustbox::{ansi, Color, RB_BOLD, RB_NORMAL};
+//!
+//! let line = ansi::parse_line("\x1b[1;31merror\x1b[0m: \x1b[38;5;208mwarning");
+//! assert_eq!(line.0.len(), 3);
+//! assert_eq!((line.0[0].style, line.0[0].fg), (RB_BOLD, Color::Red));
+//! assert_eq!((line.0[1].text.as_str(), line.0[1].style), (": ", RB_NORMAL));
+//! assert_eq!(line.0[2].fg, Color::Byte(208));
+//!
+//! let line = ansi::parse_line("\x1b[4;58;5;9mtypo\x1b[59m");
+//! assert_eq!(line.0[0].underline, Color::Byte(9));
+//!
+//! let line = ansi::parse_line("\x1b[31mred\x1b(B\x1b[m plain");
+//! assert_eq!(line.0[0].text, "red");
+//! assert_eq!(line.0[1].text, " plain");
+//!
+//! let line = ansi::parse_line("a\tb\x07");
+//! assert_eq!(line.0[0].text, "a       b");
+//!
+//! let line = ansi::parse_line("\x1b[>4;2mkeys\u{9b}1mbold\u{85}");
+//! assert_eq!((line.0[0].text.as_str(), line.0[0].style), ("keys", RB_NORMAL));
+//! assert_eq!((line.0[1].text.as_str(), line.0[1].style), ("bold", RB_BOLD));
+//! ```
+
+use style::RB_ANY_UNDERLINE;
+use text;
+use super::{Color, Line, Span, Style};
+use super::{RB_BLINK, RB_BOLD, RB_DIM, RB_INVISIBLE, RB_ITALIC, RB_NORMAL, RB_REVERSE, RB_STRIKETHROUGH};
+use super::{RB_CURLY_UNDERLINE, RB_DASHED_UNDERLINE, RB_DOTTED_UNDERLINE, RB_DOUBLE_UNDERLINE, RB_UNDERLINE};
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+// The 8-bit form of `ESC [`.
+const CSI: char = '\u{9b}';
+
+/// Parse `s` into one line of spans. Line breaks are left out, like other control characters.
+pub fn parse_line(s: &str) -> Line {
+    let mut parser = Parser::new();
+    parser.feed(s);
+    parser.finish()
+}
+
+/// Parse `s` into a line of spans for each of its lines. Styles carry over from one line to the
+/// next, as they do in a terminal.
+///
+/// ```
+/// use rustbox::{ansi, Color};
+///
+/// let lines = ansi::parse("\x1b[32m+added\r\n+more\x1b[m\n");
+/// assert_eq!(lines.len(), 2);
+/// assert_eq!(lines[1].0[0].fg, Color::Green);
+/// ```
+pub fn parse(s: &str) -> Vec<Line> {
+    let mut parser = Parser::new();
+    s.lines()
+        .map(|line| {
+            parser.feed(line);
+            parser.finish()
+        })
+        .collect()
+}
+
+struct Parser {
+    fg: Color,
+    bg: Color,
+    underline: Color,
+    style: Style,
+    text: String,
+    spans: Vec<Span>,
+    // The width of the spans on this line so far, for tab stops.
+    column: usize,
+}
+
+impl Parser {
+    fn new() -> Parser {
+        Parser {
+            fg: Color::Default,
+            bg: Color::Default,
+            underline: Color::Default,
+            style: RB_NORMAL,
+            text: String::new(),
+            spans: Vec::new(),
+            column: 0,
+        }
+    }
+
+    fn feed(&mut self, s: &str) {
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            let next = match ch {
+                ESC => chars.next(),
+                CSI => Some('['),
+                '\t' => {
+                    let column = self.column + text::str_width(&self.text);
+                    for _ in 0..8 - column % 8 {
+                        self.text.push(' ');
+                    }
+                    continue;
+                }
+                // C0 and C1 controls, and DEL between them.
+                '\x00'..='\x1f' | '\x7f'..='\u{9f}' => continue,
+                _ => {
+                    self.text.push(ch);
+                    continue;
+                }
+            };
+            match next {
+                // CSI: parameters and intermediate bytes, up to a final byte. It is only SGR
+                // without a private prefix such as `>` or intermediate bytes, as in `ESC [ > 4 m`.
+                Some('[') => {
+                    let mut params = String::new();
+                    for ch in chars.by_ref() {
+                        match ch {
+                            '\x40'..='\x7e' => {
+                                let plain = params.chars().all(|c| c.is_ascii_digit() || c == ';' || c == ':');
+                                if ch == 'm' && plain {
+                                    self.sgr(&params);
+                                }
+                                break;
+                            }
+                            _ => params.push(ch),
+                        }
+                    }
+                }
+                // OSC, DCS and the like: a string up to BEL or `ESC \`.
+                Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                    while let Some(ch) = chars.next() {
+                        if ch == BEL {
+                            break;
+                        }
+                        if ch == ESC && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // nF sequences: intermediate bytes, up to a final byte.
+                Some('\x20'..='\x2f') => while let Some('\x20'..='\x2f') = chars.next() {},
+                // Anything else is two characters long.
+                _ => (),
+            }
+        }
+    }
+
+    // The spans fed since the last call.
+    fn finish(&mut self) -> Line {
+        self.flush();
+        self.column = 0;
+        Line(self.spans.drain(..).collect())
+    }
+
+    // End the current span, before the style changes.
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = self.text.split_off(0);
+            self.column += text::str_width(&text);
+            self.spans.push(Span {
+                text,
+                fg: self.fg,
+                bg: self.bg,
+                style: self.style,
+                underline: self.underline,
+            });
+        }
+    }
+
+    fn sgr(&mut self, params: &str) {
+        self.flush();
+        // Parameters are separated by `;`, and may have sub-parameters separated by `:`. Missing
+        // ones count as 0.
+        let mut params = params.split(';')
+            .map(|param| param.split(':').map(|n| n.parse().unwrap_or(0)).collect::<Vec<u32>>());
+        while let Some(param) = params.next() {
+            match param[0] {
+                0 => {
+                    self.fg = Color::Default;
+                    self.bg = Color::Default;
+                    self.underline = Color::Default;
+                    self.style = RB_NORMAL;
+                }
+                1 => self.style.insert(RB_BOLD),
+                2 => self.style.insert(RB_DIM),
+                3 => self.style.insert(RB_ITALIC),
+                4 => {
+                    self.style.remove(RB_ANY_UNDERLINE);
+                    self.style.insert(match param.get(1) {
+                        None | Some(&1) => RB_UNDERLINE,
+                        Some(&2) => RB_DOUBLE_UNDERLINE,
+                        Some(&3) => RB_CURLY_UNDERLINE,
+                        Some(&4) => RB_DOTTED_UNDERLINE,
+                        Some(&5) => RB_DASHED_UNDERLINE,
+                        Some(_) => RB_NORMAL,
+                    });
+                }
+                5 | 6 => self.style.insert(RB_BLINK),
+                7 => self.style.insert(RB_REVERSE),
+                8 => self.style.insert(RB_INVISIBLE),
+                9 => self.style.insert(RB_STRIKETHROUGH),
+                21 => {
+                    self.style.remove(RB_ANY_UNDERLINE);
+                    self.style.insert(RB_DOUBLE_UNDERLINE);
+                }
+                22 => self.style.remove(RB_BOLD | RB_DIM),
+                23 => self.style.remove(RB_ITALIC),
+                24 => self.style.remove(RB_ANY_UNDERLINE),
+                25 => self.style.remove(RB_BLINK),
+                27 => self.style.remove(RB_REVERSE),
+                28 => self.style.remove(RB_INVISIBLE),
+                29 => self.style.remove(RB_STRIKETHROUGH),
+                n @ 30..=37 => self.fg = Color::from_256color((n - 30) as u16),
+                n @ 40..=47 => self.bg = Color::from_256color((n - 40) as u16),
+                n @ 90..=97 => self.fg = Color::Byte((n - 90 + 8) as u16),
+                n @ 100..=107 => self.bg = Color::Byte((n - 100 + 8) as u16),
+                38 => self.fg = extended_color(&param[1..], &mut params).unwrap_or(self.fg),
+                48 => self.bg = extended_color(&param[1..], &mut params).unwrap_or(self.bg),
+                58 => self.underline = extended_color(&param[1..], &mut params).unwrap_or(self.underline),
+                39 => self.fg = Color::Default,
+                49 => self.bg = Color::Default,
+                59 => self.underline = Color::Default,
+                _ => (),
+            }
+        }
+    }
+}
+
+// The color of a 38, 48 or 58 parameter: `5;n` or `2;r;g;b` in the parameters that follow, or
+// the same in sub-parameters, where a color space id may come before the 24-bit color.
+fn extended_color<I>(sub: &[u32], params: &mut I) -> Option<Color>
+    where I: Iterator<Item = Vec<u32>>
+{
+    let values: Vec<u32> = if sub.is_empty() {
+        match params.next().map(|param| param[0]) {
+            Some(5) => params.next().map(|param| vec![5, param[0]]).unwrap_or_default(),
+            Some(2) => {
+                let rgb: Vec<u32> = params.by_ref().take(3).map(|param| param[0]).collect();
+                [2].iter().cloned().chain(rgb).collect()
+            }
+            _ => return None,
+        }
+    } else if sub[0] == 2 && sub.len() > 4 {
+        vec![2, sub[2], sub[3], sub[4]]
+    } else {
+        sub.to_vec()
+    };
+    match values[..] {
+        [5, n] if n < 256 => Some(Color::from_256color(n as u16)),
+        [2, r, g, b] if r < 256 && g < 256 && b < 256 => Some(Color::Rgb(r as u8, g as u8, b as u8)),
+        _ => None,
+    }
+}
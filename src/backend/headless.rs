@@ -0,0 +1,212 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::Backend;
+use {Cell, Color, Event, EventResult, InitError, InputMode, OutputMode, RB_NORMAL};
+
+const EMPTY_CELL: Cell = Cell {
+    ch: ' ',
+    fg: Color::Default,
+    bg: Color::Default,
+    style: RB_NORMAL,
+    underline: Color::Default,
+};
+
+struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    // The rest of the grapheme cluster in cells that hold more than one `char`, by index.
+    rest: HashMap<usize, String>,
+    cursor: Option<(usize, usize)>,
+}
+
+/// A backend that draws into an in-memory grid instead of the terminal.
+///
+/// It needs no TTY, and any number of them can exist next to each other and next to a
+/// terminal-backed `RustBox`, which makes it suitable for tests. It has no input of its own:
+/// events come from `RustBox::push_event`, or from `push_input` to stand in for the terminal.
+pub struct HeadlessBackend {
+    grid: Mutex<Grid>,
+
+    // Queued by `push_input`, returned by the next polls.
+    input: Mutex<VecDeque<Event>>,
+
+    // Set by `wake`, and cleared by the poll that it interrupts.
+    woken: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl HeadlessBackend {
+    pub fn new(width: usize, height: usize) -> HeadlessBackend {
+        HeadlessBackend {
+            grid: Mutex::new(Grid {
+                width,
+                height,
+                cells: vec![EMPTY_CELL; width * height],
+                rest: HashMap::new(),
+                cursor: None,
+            }),
+            input: Mutex::new(VecDeque::new()),
+            woken: Mutex::new(false),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    /// Queue an event as if it had been read from the terminal, and wake up a poll waiting for
+    /// one. Unlike `RustBox::push_event`, this goes through the same handling as real input,
+    /// such as Ctrl-Z with `InitOptions::job_control`.
+    pub fn push_input(&self, ev: Event) {
+        match self.input.lock() {
+            Ok(mut input) => input.push_back(ev),
+            Err(poisoned) => poisoned.into_inner().push_back(ev),
+        }
+        self.wake();
+    }
+
+    /// The position last passed to `set_cursor`, or `None` if the cursor is hidden.
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        self.grid().cursor
+    }
+
+    fn grid(&self) -> MutexGuard<'_, Grid> {
+        match self.grid.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn take_input(&self) -> Option<Event> {
+        match self.input.lock() {
+            Ok(mut input) => input.pop_front(),
+            Err(poisoned) => poisoned.into_inner().pop_front(),
+        }
+    }
+
+    // Return queued input, or block until woken up, or until `timeout` has elapsed if one is
+    // given.
+    fn wait(&self, timeout: Option<Duration>) -> EventResult {
+        if let Some(ev) = self.take_input() {
+            return Ok(ev);
+        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut woken = match self.woken.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        while !*woken {
+            woken = match deadline {
+                None => match self.wakeup.wait(woken) {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                },
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    match self.wakeup.wait_timeout(woken, deadline - now) {
+                        Ok((guard, _)) => guard,
+                        Err(poisoned) => poisoned.into_inner().0,
+                    }
+                }
+            };
+        }
+        *woken = false;
+        Ok(self.take_input().unwrap_or(Event::NoEvent))
+    }
+}
+
+impl Backend for HeadlessBackend {
+    fn width(&self) -> usize {
+        self.grid().width
+    }
+
+    fn height(&self) -> usize {
+        self.grid().height
+    }
+
+    fn clear(&self) {
+        let mut grid = self.grid();
+        for cell in &mut grid.cells {
+            *cell = EMPTY_CELL;
+        }
+        grid.rest.clear();
+    }
+
+    fn present(&self) {
+        // The grid is the screen; there is nothing to flush.
+    }
+
+    fn set_cursor(&self, x: isize, y: isize) {
+        self.grid().cursor = if x < 0 || y < 0 { None } else { Some((x as usize, y as usize)) };
+    }
+
+    fn set_cell(&self, x: usize, y: usize, cell: Cell) {
+        let mut grid = self.grid();
+        if x < grid.width && y < grid.height {
+            let width = grid.width;
+            grid.cells[y * width + x] = cell;
+            grid.rest.remove(&(y * width + x));
+        }
+    }
+
+    fn set_cluster(&self, x: usize, y: usize, cell: Cell, rest: &str) {
+        let mut grid = self.grid();
+        if x < grid.width && y < grid.height {
+            let i = y * grid.width + x;
+            grid.cells[i] = cell;
+            if rest.is_empty() {
+                grid.rest.remove(&i);
+            } else {
+                grid.rest.insert(i, rest.to_owned());
+            }
+        }
+    }
+
+    fn get_cell(&self, x: usize, y: usize) -> Option<Cell> {
+        let grid = self.grid();
+        if x < grid.width && y < grid.height {
+            Some(grid.cells[y * grid.width + x])
+        } else {
+            None
+        }
+    }
+
+    fn get_cluster(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        let grid = self.grid();
+        if x < grid.width && y < grid.height {
+            let i = y * grid.width + x;
+            Some((grid.cells[i], grid.rest.get(&i).cloned().unwrap_or_default()))
+        } else {
+            None
+        }
+    }
+
+    fn poll_event(&self, _raw: bool) -> EventResult {
+        self.wait(None)
+    }
+
+    fn peek_event(&self, timeout: Duration, _raw: bool) -> EventResult {
+        self.wait(Some(timeout))
+    }
+
+    fn set_input_mode(&self, _mode: InputMode) {}
+
+    fn set_output_mode(&self, _mode: OutputMode) {}
+
+    fn suspend(&self) {}
+
+    fn resume(&self) -> Result<(), InitError> {
+        Ok(())
+    }
+
+    fn wake(&self) {
+        match self.woken.lock() {
+            Ok(mut guard) => *guard = true,
+            Err(poisoned) => *poisoned.into_inner() = true,
+        }
+        self.wakeup.notify_all();
+    }
+}
@@ -0,0 +1,212 @@
+//! The interface between `RustBox` and whatever it draws to and reads input from.
+//!
+//! `RustBox` takes care of locking, queued events and the conversions done by its drawing
+//! methods; a backend only has to deal with individual cells and events. `RustBox` never calls
+//! two output methods, or two input methods, at the same time, but it may call an input method
+//! while an output method is running on another thread.
+
+use std::char;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use libc;
+
+use super::{Cell, Event, EventError, EventResult, InitError, InputMode, Key, Modifiers, OutputMode};
+
+pub use self::headless::HeadlessBackend;
+#[cfg(feature = "pure")]
+pub use self::pure::PureBackend;
+#[cfg(feature = "termbox")]
+pub use self::termbox::TermboxBackend;
+
+// How long `Backend::wait_event` waits for input at a time before checking the file
+// descriptors again.
+const FD_INTERVAL: Duration = Duration::from_millis(10);
+
+mod headless;
+#[cfg(feature = "pure")]
+mod pure;
+#[cfg(feature = "termbox")]
+mod termbox;
+
+/// Make a blocked wait of every open terminal backend return, as `Backend::wake` does. Safe to
+/// call from a signal handler.
+pub(crate) fn wake_ttys() {
+    #[cfg(feature = "pure")]
+    pure::wake_ttys();
+    #[cfg(feature = "termbox")]
+    termbox::wake();
+}
+
+/// The backend `RustBox::init` uses: termbox if it is enabled, the pure Rust one otherwise.
+#[cfg(feature = "termbox")]
+pub type DefaultBackend = TermboxBackend;
+#[cfg(all(feature = "pure", not(feature = "termbox")))]
+pub type DefaultBackend = PureBackend;
+
+pub trait Backend {
+    /// Width of the screen, in cells.
+    fn width(&self) -> usize;
+
+    /// Height of the screen, in cells.
+    fn height(&self) -> usize;
+
+    /// Reset every cell of the back buffer.
+    fn clear(&self);
+
+    /// Make the screen show the contents of the back buffer.
+    fn present(&self);
+
+    /// Move the cursor. Negative coordinates hide it.
+    fn set_cursor(&self, x: isize, y: isize);
+
+    /// Write a cell of the back buffer. Cells outside of the screen are ignored.
+    fn set_cell(&self, x: usize, y: usize, cell: Cell);
+
+    /// Write a cell of the back buffer that holds a grapheme cluster of more than one `char`:
+    /// `cell.ch` followed by `rest`, such as combining marks or the rest of an emoji sequence.
+    ///
+    /// Backends that can't show them draw only `cell.ch`.
+    fn set_cluster(&self, x: usize, y: usize, cell: Cell, _rest: &str) {
+        self.set_cell(x, y, cell);
+    }
+
+    /// Read a cell of the back buffer, or `None` if it is outside of the screen.
+    fn get_cell(&self, x: usize, y: usize) -> Option<Cell>;
+
+    /// Read a cell of the back buffer along with the rest of its grapheme cluster, as written
+    /// by `set_cluster`, or `None` if it is outside of the screen.
+    ///
+    /// Backends that only keep `cell.ch` return an empty rest.
+    fn get_cluster(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        self.get_cell(x, y).map(|cell| (cell, String::new()))
+    }
+
+    /// Read what the screen showed at (`x`, `y`) as of the last `present`, as `get_cluster`
+    /// does for the back buffer, or `None` if it is outside of the screen or isn't known.
+    ///
+    /// The default implementation reads the back buffer, which is only right for backends
+    /// that draw straight to the screen.
+    fn get_presented(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        self.get_cluster(x, y)
+    }
+
+    /// Block until there is an event.
+    ///
+    /// `Event::NoEvent` may be returned after a call to `wake`.
+    fn poll_event(&self, raw: bool) -> EventResult;
+
+    /// Wait up to `timeout` for an event, returning `Event::NoEvent` if there was none.
+    fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult;
+
+    fn set_input_mode(&self, mode: InputMode);
+
+    fn set_output_mode(&self, mode: OutputMode);
+
+    /// Turn bracketed paste on or off, if the backend is able to report `Event::Paste`.
+    fn set_bracketed_paste(&self, _enabled: bool) {}
+
+    /// Turn focus reporting on or off, if the backend is able to report `Event::FocusGained`
+    /// and `Event::FocusLost`.
+    fn set_focus_events(&self, _enabled: bool) {}
+
+    /// Give the terminal back to the user, as for `RustBox::suspend`.
+    fn suspend(&self);
+
+    /// Take the terminal over again after `suspend`, with the input and output modes that were
+    /// set before.
+    fn resume(&self) -> Result<(), InitError>;
+
+    /// Make a blocked `poll_event`, `peek_event` or `wait_event` return early, if the backend is
+    /// able to.
+    fn wake(&self) {}
+
+    /// Something that puts the terminal back the way it was found, for a panic hook to call
+    /// where the backend itself can't be reached. Once it has run, dropping the backend mustn't
+    /// touch the terminal again.
+    fn restorer(&self) -> Option<Box<Fn() + Send + Sync>> {
+        None
+    }
+
+    /// Like `peek_event`, or `poll_event` if there is no `timeout`, but also return
+    /// `Event::Readable(token)` as soon as one of `fds`, given with their tokens, is ready to be
+    /// read.
+    ///
+    /// The default implementation checks `fds` in between calls to `peek_event` of 10ms each,
+    /// so it keeps waking up for as long as there are any, and notices them up to 10ms late.
+    /// Backends that can should wait on them together with their own input instead, as the
+    /// termbox and pure Rust backends do.
+    fn wait_event(&self, timeout: Option<Duration>, raw: bool, fds: &[(RawFd, usize)]) -> EventResult {
+        if fds.is_empty() {
+            return match timeout {
+                Some(timeout) => self.peek_event(timeout, raw),
+                None => self.poll_event(raw),
+            };
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(token) = try!(readable(fds).map_err(io_error)) {
+                return Ok(Event::Readable(token));
+            }
+            let now = Instant::now();
+            let wait = match deadline {
+                Some(deadline) if deadline <= now => return Ok(Event::NoEvent),
+                Some(deadline) => FD_INTERVAL.min(deadline - now),
+                None => FD_INTERVAL,
+            };
+            match try!(self.peek_event(wait, raw)) {
+                Event::NoEvent => continue,
+                ev => return Ok(ev),
+            }
+        }
+    }
+}
+
+/// The token of one of `fds` that is ready to be read, if there is one, without waiting.
+fn readable(fds: &[(RawFd, usize)]) -> io::Result<Option<usize>> {
+    let mut pollfds: Vec<libc::pollfd> = fds.iter()
+        .map(|&(fd, _)| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+        .collect();
+    let n = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, 0) };
+    if n < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::Interrupted {
+            return Ok(None);
+        }
+        return Err(err);
+    }
+    // A hangup or an error counts as readable too, so that whoever reads finds out about it.
+    Ok(pollfds.iter().zip(fds).find(|&(pollfd, _)| pollfd.revents != 0).map(|(_, &(_, token))| token))
+}
+
+fn io_error(err: io::Error) -> EventError {
+    EventError::Unknown(err.raw_os_error().unwrap_or(-1) as isize)
+}
+
+/// Build a key event from its termbox representation. `emod` holds `Modifiers` bits, of which
+/// termbox itself only ever sets `MOD_ALT`.
+///
+/// if the `raw` parameter is true, then the Event variant will be the raw
+/// representation of the event.
+///     for instance KeyEventRaw instead of KeyEvent
+///
+/// This is useful if you want to interpret the raw event data yourself, rather
+/// than having rustbox translate it to its own representation.
+fn key_event(emod: u8, key: u16, ch: u32, raw: bool) -> Event {
+    if raw {
+        Event::KeyEventRaw(emod, key, ch)
+    } else {
+        let k = match key {
+            0 => char::from_u32(ch).map(Key::Char),
+            a => Key::from_code(a),
+        };
+        let mods = Modifiers::from_bits_truncate(emod);
+        if let Some(key) = k {
+            Event::KeyEvent(key, mods)
+        }
+        else {
+            Event::KeyEvent(Key::Unknown(key), mods)
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Turns the bytes read from the terminal into events.
+
+use std::time::{Duration, Instant};
+
+use super::super::key_event;
+use super::terminfo::{self, Terminfo};
+use {Button, Event, Mouse, MOD_ALT, MOD_CTRL, MOD_NONE, MOD_SHIFT};
+
+const ESC: u8 = 0x1b;
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+// How long to wait for the rest of an escape sequence before taking the escape byte to be the
+// Esc key.
+const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+
+// Sequences that xterm-alikes send whatever their terminfo entry says, paired with their key
+// index (see `terminfo::KEYS`).
+const FALLBACK_KEYS: &[(&[u8], usize)] = &[
+    (b"\x1b[11~", 0), (b"\x1b[12~", 1), (b"\x1b[13~", 2), (b"\x1b[14~", 3),
+    (b"\x1b[[A", 0), (b"\x1b[[B", 1), (b"\x1b[[C", 2), (b"\x1b[[D", 3), (b"\x1b[[E", 4),
+    (b"\x1b[2~", 12), (b"\x1b[3~", 13),
+    (b"\x1b[H", 14), (b"\x1bOH", 14), (b"\x1b[1~", 14), (b"\x1b[7~", 14),
+    (b"\x1b[F", 15), (b"\x1bOF", 15), (b"\x1b[4~", 15), (b"\x1b[8~", 15),
+    (b"\x1b[5~", 16), (b"\x1b[6~", 17),
+    (b"\x1b[A", 18), (b"\x1bOA", 18), (b"\x1b[B", 19), (b"\x1bOB", 19),
+    (b"\x1b[D", 20), (b"\x1bOD", 20), (b"\x1b[C", 21), (b"\x1bOC", 21),
+];
+
+enum Parsed {
+    Event(Event),
+    // The buffer holds the start of something longer; wait for more input.
+    Incomplete,
+}
+
+pub struct Parser {
+    buf: Vec<u8>,
+    // Escape sequences of special keys, with their termbox key codes.
+    keys: Vec<(Vec<u8>, u16)>,
+    // When the buffer started with what may be the start of an escape sequence.
+    escape_since: Option<Instant>,
+}
+
+impl Parser {
+    pub fn new(info: &Terminfo) -> Parser {
+        let mut keys: Vec<(Vec<u8>, u16)> = Vec::new();
+        for (i, &cap) in terminfo::KEYS.iter().enumerate() {
+            if let Some(seq) = info.string(cap) {
+                if !seq.is_empty() {
+                    keys.push((seq.to_vec(), 0xFFFF - i as u16));
+                }
+            }
+        }
+        for &(seq, i) in FALLBACK_KEYS {
+            if !keys.iter().any(|known| &known.0[..] == seq) {
+                keys.push((seq.to_vec(), 0xFFFF - i as u16));
+            }
+        }
+        // Longest first, so that a sequence never shadows one it is a prefix of.
+        keys.sort_by_key(|key| !key.0.len());
+        Parser { buf: Vec::new(), keys, escape_since: None }
+    }
+
+    /// The buffer that input should be appended to.
+    pub fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+
+    /// Take the next complete event out of the buffer, if there is one.
+    ///
+    /// `alt` selects `InputMode::Alt` handling of unknown escape sequences: the escape byte is
+    /// taken to mean that Alt was held for the next key, rather than being the Esc key itself.
+    pub fn next(&mut self, raw: bool, alt: bool) -> Option<Event> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match self.parse(raw, alt) {
+            Some((Parsed::Event(ev), len)) => {
+                self.buf.drain(..len);
+                self.escape_since = None;
+                Some(ev)
+            }
+            Some((Parsed::Incomplete, _)) => {
+                // A paste can take as long as it likes.
+                if self.escape_since.is_none() && !self.buf.starts_with(PASTE_START) {
+                    self.escape_since = Some(Instant::now());
+                }
+                None
+            }
+            None => {
+                // Garbage that can't be decoded; drop a byte and carry on.
+                self.buf.remove(0);
+                self.next(raw, alt)
+            }
+        }
+    }
+
+    /// When to give up waiting for the rest of an escape sequence and call `flush`, if the
+    /// buffer holds the start of one.
+    pub fn escape_deadline(&self) -> Option<Instant> {
+        self.escape_since.map(|since| since + ESC_TIMEOUT)
+    }
+
+    /// Take the escape byte at the start of the buffer as the Esc key, the rest of the sequence
+    /// having never come.
+    pub fn flush(&mut self, raw: bool) -> Option<Event> {
+        self.escape_since.take()?;
+        self.buf.remove(0);
+        Some(key_event(0, 0x1b, 0, raw))
+    }
+
+    fn parse(&self, raw: bool, alt: bool) -> Option<(Parsed, usize)> {
+        let buf = &self.buf[..];
+        if buf[0] != ESC {
+            return self.parse_key(buf, 0, raw);
+        }
+        if buf.len() == 1 {
+            return Some((Parsed::Incomplete, 0));
+        }
+        if let Some(paste) = parse_paste(buf) {
+            return Some(paste);
+        }
+        // Focus reports.
+        if buf.starts_with(b"\x1b[I") {
+            return Some((Parsed::Event(Event::FocusGained), 3));
+        }
+        if buf.starts_with(b"\x1b[O") {
+            return Some((Parsed::Event(Event::FocusLost), 3));
+        }
+        if let Some(mouse) = parse_mouse(buf) {
+            return Some(mouse);
+        }
+        for &(ref seq, key) in &self.keys {
+            if buf.starts_with(seq) {
+                return Some((Parsed::Event(key_event(0, key, 0, raw)), seq.len()));
+            }
+        }
+        if let Some(key) = self.parse_modified_key(buf, raw) {
+            return Some(key);
+        }
+        if self.is_cut_short(buf) {
+            return Some((Parsed::Incomplete, 0));
+        }
+        if alt {
+            return self.parse_key(&buf[1..], MOD_ALT.bits(), raw).map(|(parsed, len)| (parsed, len + 1));
+        }
+        Some((Parsed::Event(key_event(0, 0x1b, 0, raw)), 1))
+    }
+
+    // Whether `buf` may be the start of a key sequence or report that isn't all there yet.
+    fn is_cut_short(&self, buf: &[u8]) -> bool {
+        let numbers = buf.starts_with(b"\x1b[") && buf[2..].iter().all(|&c| c.is_ascii_digit() || c == b';');
+        numbers || PASTE_START.starts_with(buf) || self.keys.iter().any(|key| key.0.starts_with(buf))
+    }
+
+    // A single, possibly multi-byte, character or control key.
+    fn parse_key(&self, buf: &[u8], emod: u8, raw: bool) -> Option<(Parsed, usize)> {
+        let first = buf[0];
+        if first <= 0x20 || first == 0x7f {
+            return Some((Parsed::Event(key_event(emod, first as u16, 0, raw)), 1));
+        }
+        let len = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return None,
+        };
+        if buf.len() < len {
+            return Some((Parsed::Incomplete, 0));
+        }
+        let ch = ::std::str::from_utf8(&buf[..len]).ok()?.chars().next()?;
+        Some((Parsed::Event(key_event(emod, 0, ch as u32, raw)), len))
+    }
+
+    // A special key with modifiers, which xterm-alikes send as `ESC [ 1 ; m X` or `ESC [ n ; m ~`
+    // where `ESC [ X`, `ESC O X` or `ESC [ n ~` is the key on its own. Shift+Tab has a sequence of
+    // its own.
+    fn parse_modified_key(&self, buf: &[u8], raw: bool) -> Option<(Parsed, usize)> {
+        if buf.starts_with(b"\x1b[Z") {
+            return Some((Parsed::Event(key_event(MOD_SHIFT.bits(), 0x09, 0, raw)), 3));
+        }
+        if !buf.starts_with(b"\x1b[") {
+            return None;
+        }
+        let semicolon = 2 + buf[2..].iter().position(|&c| c == b';')?;
+        let end = semicolon + 1 + buf[semicolon + 1..].iter().position(|&c| !c.is_ascii_digit())?;
+        let number = &buf[2..semicolon];
+        if !number.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let param: u8 = ::std::str::from_utf8(&buf[semicolon + 1..end]).ok()?.parse().ok()?;
+        let last = buf[end];
+
+        let plain: Vec<Vec<u8>> = if last == b'~' {
+            vec![[b"\x1b[", number, b"~"].concat()]
+        } else {
+            vec![vec![ESC, b'[', last], vec![ESC, b'O', last]]
+        };
+        let &(_, key) = self.keys.iter().find(|key| plain.contains(&key.0))?;
+
+        // The parameter is one more than a mask of Shift (1), Alt (2) and Ctrl (4). Some
+        // terminals also have Meta (8), which is as good as Alt here.
+        let mask = param.saturating_sub(1);
+        let mut mods = MOD_NONE;
+        for &(bit, modifier) in &[(1, MOD_SHIFT), (2, MOD_ALT), (4, MOD_CTRL), (8, MOD_ALT)] {
+            if mask & bit != 0 {
+                mods = mods | modifier;
+            }
+        }
+        Some((Parsed::Event(key_event(mods.bits(), key, 0, raw)), end + 1))
+    }
+}
+
+// Text between the markers of bracketed paste mode.
+fn parse_paste(buf: &[u8]) -> Option<(Parsed, usize)> {
+    if !buf.starts_with(PASTE_START) {
+        return None;
+    }
+    let text = &buf[PASTE_START.len()..];
+    match text.windows(PASTE_END.len()).position(|w| w == PASTE_END) {
+        Some(end) => {
+            // Terminals send line breaks as they would for the Enter key.
+            let paste = String::from_utf8_lossy(&text[..end]).replace("\r\n", "\n").replace('\r', "\n");
+            Some((Parsed::Event(Event::Paste(paste)), PASTE_START.len() + end + PASTE_END.len()))
+        }
+        None => Some((Parsed::Incomplete, 0)),
+    }
+}
+
+// Mouse reports in the X10 (`ESC [ M b x y`), SGR (`ESC [ < b ; x ; y M`) and urxvt
+// (`ESC [ b ; x ; y M`) encodings.
+fn parse_mouse(buf: &[u8]) -> Option<(Parsed, usize)> {
+    if !buf.starts_with(b"\x1b[") {
+        return None;
+    }
+    if buf.starts_with(b"\x1b[M") {
+        if buf.len() < 6 {
+            return Some((Parsed::Incomplete, 0));
+        }
+        let b = buf[3].wrapping_sub(32) as u32;
+        let x = buf[4].wrapping_sub(33) as i32;
+        let y = buf[5].wrapping_sub(33) as i32;
+        return Some((Parsed::Event(mouse_event(b, false, x, y)), 6));
+    }
+
+    let sgr = buf.get(2) == Some(&b'<');
+    let start = if sgr { 3 } else { 2 };
+    let mut fields = [0u32; 3];
+    let mut field = 0;
+    let mut seen_digit = false;
+    for (i, &c) in buf.iter().enumerate().skip(start) {
+        match c {
+            b'0'..=b'9' => {
+                fields[field] = fields[field].saturating_mul(10).saturating_add((c - b'0') as u32);
+                seen_digit = true;
+            }
+            b';' if field < 2 && seen_digit => {
+                field += 1;
+                seen_digit = false;
+            }
+            b'M' | b'm' if field == 2 && seen_digit && (sgr || c == b'M') => {
+                // urxvt offsets the button by 32, like X10.
+                let b = if sgr { fields[0] } else { fields[0].saturating_sub(32) };
+                let (x, y) = (fields[1] as i32 - 1, fields[2] as i32 - 1);
+                return Some((Parsed::Event(mouse_event(b, c == b'm', x, y)), i + 1));
+            }
+            _ => return None,
+        }
+    }
+    // Only an SGR report is unambiguous before it is complete.
+    if sgr { Some((Parsed::Incomplete, 0)) } else { None }
+}
+
+// `b` is the button code shared by all encodings: the low two bits are the button, 4, 8 and 16
+// are Shift, Alt and Ctrl, 32 is motion and 64 the wheel.
+fn mouse_event(b: u32, release: bool, x: i32, y: i32) -> Event {
+    let motion = b & 32 != 0;
+    let mouse = match (b & !(4 | 8 | 16 | 32), motion) {
+        _ if release => Mouse::Release,
+        (0, false) => Mouse::Left,
+        (1, false) => Mouse::Middle,
+        (2, false) => Mouse::Right,
+        (3, false) => Mouse::Release,
+        (0, true) => Mouse::Drag(Button::Left),
+        (1, true) => Mouse::Drag(Button::Middle),
+        (2, true) => Mouse::Drag(Button::Right),
+        (3, true) => Mouse::Move,
+        (64, _) => Mouse::WheelUp,
+        (65, _) => Mouse::WheelDown,
+        (66, _) => Mouse::WheelLeft,
+        (67, _) => Mouse::WheelRight,
+        (code, _) => Mouse::Unknown(code as u16),
+    };
+    let mut mods = MOD_NONE;
+    for &(bit, modifier) in &[(4, MOD_SHIFT), (8, MOD_ALT), (16, MOD_CTRL)] {
+        if b & bit != 0 {
+            mods = mods | modifier;
+        }
+    }
+    Event::MouseEvent(mouse, mods, x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use super::super::terminfo::Terminfo;
+    use {Button, Event, Key, Modifiers, Mouse, MOD_ALT, MOD_CTRL, MOD_NONE, MOD_SHIFT};
+
+    // Feed `input` to a parser for xterm in one go, and take out every event that comes of it.
+    fn parse(input: &[u8], alt: bool) -> Vec<Event> {
+        let mut parser = Parser::new(&Terminfo::xterm());
+        feed(&mut parser, input, alt)
+    }
+
+    fn feed(parser: &mut Parser, input: &[u8], alt: bool) -> Vec<Event> {
+        parser.buffer().extend_from_slice(input);
+        let mut events = Vec::new();
+        while let Some(ev) = parser.next(false, alt) {
+            events.push(ev);
+        }
+        events
+    }
+
+    fn keys(events: &[Event]) -> Vec<(Key, Modifiers)> {
+        events.iter()
+            .map(|ev| match *ev {
+                Event::KeyEvent(key, mods) => (key, mods),
+                ref ev => panic!("expected a key event, got {:?}", ev),
+            })
+            .collect()
+    }
+
+    fn mice(events: &[Event]) -> Vec<(Mouse, Modifiers, i32, i32)> {
+        events.iter()
+            .map(|ev| match *ev {
+                Event::MouseEvent(mouse, mods, x, y) => (mouse, mods, x, y),
+                ref ev => panic!("expected a mouse event, got {:?}", ev),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn characters_and_control_keys() {
+        assert_eq!(keys(&parse(b"a\r\x7f\x01 ", false)), [
+            (Key::Char('a'), MOD_NONE),
+            (Key::Enter, MOD_NONE),
+            (Key::Backspace, MOD_NONE),
+            (Key::Ctrl('a'), MOD_NONE),
+            (Key::Char(' '), MOD_NONE),
+        ]);
+        assert_eq!(keys(&parse("日é".as_bytes(), false)), [(Key::Char('日'), MOD_NONE), (Key::Char('é'), MOD_NONE)]);
+    }
+
+    #[test]
+    fn split_utf8() {
+        let mut parser = Parser::new(&Terminfo::xterm());
+        let bytes = "日".as_bytes();
+        assert!(feed(&mut parser, &bytes[..2], false).is_empty());
+        assert_eq!(keys(&feed(&mut parser, &bytes[2..], false)), [(Key::Char('日'), MOD_NONE)]);
+    }
+
+    #[test]
+    fn invalid_bytes_are_dropped() {
+        assert_eq!(keys(&parse(b"\xff\x80a", false)), [(Key::Char('a'), MOD_NONE)]);
+    }
+
+    #[test]
+    fn special_keys() {
+        // From the terminfo entry, and the ones xterm-alikes send whatever it says.
+        assert_eq!(keys(&parse(b"\x1bOA\x1b[A\x1b[15~\x1b[11~\x1b[H\x1b[3~", false)), [
+            (Key::Up, MOD_NONE),
+            (Key::Up, MOD_NONE),
+            (Key::F(5), MOD_NONE),
+            (Key::F(1), MOD_NONE),
+            (Key::Home, MOD_NONE),
+            (Key::Delete, MOD_NONE),
+        ]);
+    }
+
+    #[test]
+    fn modified_keys() {
+        assert_eq!(keys(&parse(b"\x1b[1;5C\x1b[1;2A\x1b[3;3~\x1b[15;6~\x1b[1;9D", false)), [
+            (Key::Right, MOD_CTRL),
+            (Key::Up, MOD_SHIFT),
+            (Key::Delete, MOD_ALT),
+            (Key::F(5), MOD_SHIFT | MOD_CTRL),
+            (Key::Left, MOD_ALT),
+        ]);
+        assert_eq!(keys(&parse(b"\x1b[Z", false)), [(Key::Tab, MOD_SHIFT)]);
+    }
+
+    #[test]
+    fn raw_keys() {
+        let mut parser = Parser::new(&Terminfo::xterm());
+        parser.buffer().extend_from_slice(b"\x1b[1;5Ca");
+        match parser.next(true, false) {
+            Some(Event::KeyEventRaw(4, 0xFFEA, 0)) => (),
+            ev => panic!("expected raw Ctrl+Right, got {:?}", ev),
+        }
+        match parser.next(true, false) {
+            Some(Event::KeyEventRaw(0, 0, 0x61)) => (),
+            ev => panic!("expected raw 'a', got {:?}", ev),
+        }
+    }
+
+    #[test]
+    fn escape_and_alt() {
+        let mut parser = Parser::new(&Terminfo::xterm());
+        assert!(feed(&mut parser, b"\x1b", false).is_empty());
+        assert!(parser.escape_deadline().is_some());
+        assert_eq!(keys(&parser.flush(false).into_iter().collect::<Vec<_>>()), [(Key::Esc, MOD_NONE)]);
+        assert_eq!(parser.escape_deadline(), None);
+        // The rest of a sequence that comes in time is still taken together with the escape.
+        assert!(feed(&mut parser, b"\x1b[1;", false).is_empty());
+        assert_eq!(keys(&feed(&mut parser, b"5A", false)), [(Key::Up, MOD_CTRL)]);
+        assert!(feed(&mut parser, b"\x1bO", false).is_empty());
+        assert_eq!(keys(&parser.flush(false).into_iter().collect::<Vec<_>>()), [(Key::Esc, MOD_NONE)]);
+        assert_eq!(keys(&feed(&mut parser, b"", false)), [(Key::Char('O'), MOD_NONE)]);
+
+        assert_eq!(keys(&parse(b"\x1bx", false)), [(Key::Esc, MOD_NONE), (Key::Char('x'), MOD_NONE)]);
+        assert_eq!(keys(&parse(b"\x1bx", true)), [(Key::Char('x'), MOD_ALT)]);
+    }
+
+    #[test]
+    fn sgr_mouse() {
+        assert_eq!(mice(&parse(b"\x1b[<0;3;4M\x1b[<32;3;4M\x1b[<0;10;20m", false)), [
+            (Mouse::Left, MOD_NONE, 2, 3),
+            (Mouse::Drag(Button::Left), MOD_NONE, 2, 3),
+            (Mouse::Release, MOD_NONE, 9, 19),
+        ]);
+        assert_eq!(mice(&parse(b"\x1b[<35;1;1M\x1b[<65;1;1M\x1b[<66;1;1M\x1b[<22;1;1M", false)), [
+            (Mouse::Move, MOD_NONE, 0, 0),
+            (Mouse::WheelDown, MOD_NONE, 0, 0),
+            (Mouse::WheelLeft, MOD_NONE, 0, 0),
+            (Mouse::Right, MOD_SHIFT | MOD_CTRL, 0, 0),
+        ]);
+    }
+
+    #[test]
+    fn split_sgr_mouse() {
+        let mut parser = Parser::new(&Terminfo::xterm());
+        assert!(feed(&mut parser, b"\x1b[<2;30", false).is_empty());
+        assert_eq!(mice(&feed(&mut parser, b";40M", false)), [(Mouse::Right, MOD_NONE, 29, 39)]);
+    }
+
+    #[test]
+    fn x10_and_urxvt_mouse() {
+        assert_eq!(mice(&parse(b"\x1b[M!%(", false)), [(Mouse::Middle, MOD_NONE, 4, 7)]);
+        assert_eq!(mice(&parse(b"\x1b[M`!!", false)), [(Mouse::WheelUp, MOD_NONE, 0, 0)]);
+        assert_eq!(mice(&parse(b"\x1b[32;3;4M", false)), [(Mouse::Left, MOD_NONE, 2, 3)]);
+    }
+
+    #[test]
+    fn paste() {
+        let events = parse(b"\x1b[200~a\r\nb\rc\x1b[201~x", false);
+        match events[..] {
+            [Event::Paste(ref text), Event::KeyEvent(Key::Char('x'), _)] => assert_eq!(text, "a\nb\nc"),
+            ref events => panic!("expected a paste and a key, got {:?}", events),
+        }
+
+        let mut parser = Parser::new(&Terminfo::xterm());
+        assert!(feed(&mut parser, b"\x1b[200~\x1b[A", false).is_empty());
+        match feed(&mut parser, b"\x1b[201~", false)[..] {
+            [Event::Paste(ref text)] => assert_eq!(text, "\x1b[A"),
+            ref events => panic!("expected a paste, got {:?}", events),
+        }
+    }
+
+    #[test]
+    fn focus() {
+        match parse(b"\x1b[I\x1b[O", false)[..] {
+            [Event::FocusGained, Event::FocusLost] => (),
+            ref events => panic!("expected focus events, got {:?}", events),
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! A backend written in Rust, talking to the terminal directly instead of going through termbox.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use self::input::Parser;
+use self::terminfo::Terminfo;
+use self::tty::{Ready, Tty};
+use running::{self, RunningGuard};
+use super::{io_error, Backend};
+use {Cell, Color, Event, EventError, EventResult, InitError, InputMode, OutputMode};
+use {RB_BLINK, RB_BOLD, RB_DIM, RB_INVISIBLE, RB_ITALIC, RB_NORMAL, RB_REVERSE, RB_STRIKETHROUGH};
+use {RB_CURLY_UNDERLINE, RB_DASHED_UNDERLINE, RB_DOTTED_UNDERLINE, RB_DOUBLE_UNDERLINE, RB_UNDERLINE};
+use text::cell_width;
+
+mod input;
+mod terminfo;
+mod tty;
+
+pub use self::tty::wake_all as wake_ttys;
+
+const BLANK: Cell = Cell {
+    ch: ' ',
+    fg: Color::Default,
+    bg: Color::Default,
+    style: RB_NORMAL,
+    underline: Color::Default,
+};
+
+const MOUSE_BIT: usize = 4;
+const MOTION_BIT: usize = 8;
+const ENTER_MOUSE: &[u8] = b"\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+// Any-event tracking, on top of `ENTER_MOUSE`.
+const ENTER_MOTION: &[u8] = b"\x1b[?1003h";
+const EXIT_MOUSE: &[u8] = b"\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l";
+const ENTER_PASTE: &[u8] = b"\x1b[?2004h";
+const EXIT_PASTE: &[u8] = b"\x1b[?2004l";
+const ENTER_FOCUS: &[u8] = b"\x1b[?1004h";
+const EXIT_FOCUS: &[u8] = b"\x1b[?1004l";
+
+// Everything on the output side of the terminal.
+struct Screen {
+    width: usize,
+    height: usize,
+    back: Vec<Cell>,
+    // The rest of the grapheme cluster in cells of `back` that hold more than one `char`, by
+    // index. Few cells do.
+    back_rest: HashMap<usize, String>,
+    // What the terminal is showing, or `None` where we don't know.
+    front: Vec<Option<Cell>>,
+    front_rest: HashMap<usize, String>,
+    cursor: Option<(usize, usize)>,
+    output_mode: OutputMode,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Screen {
+        Screen {
+            width,
+            height,
+            back: vec![BLANK; width * height],
+            back_rest: HashMap::new(),
+            front: vec![None; width * height],
+            front_rest: HashMap::new(),
+            cursor: None,
+            output_mode: OutputMode::Normal,
+        }
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        let mut back = vec![BLANK; width * height];
+        let mut back_rest = HashMap::new();
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                back[y * width + x] = self.back[y * self.width + x];
+                if let Some(rest) = self.back_rest.remove(&(y * self.width + x)) {
+                    back_rest.insert(y * width + x, rest);
+                }
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.back = back;
+        self.back_rest = back_rest;
+        self.invalidate();
+    }
+
+    // Forget what the terminal shows, so that the next `present` redraws everything.
+    fn invalidate(&mut self) {
+        self.front = vec![None; self.width * self.height];
+        self.front_rest.clear();
+    }
+
+    // What brings the terminal up to date with the back buffer, after which `front` matches it.
+    fn draw(&mut self, info: &Terminfo) -> Vec<u8> {
+        let mut out = Vec::new();
+        // Where the terminal's cursor is, if we know, and the attributes last written.
+        let mut pos = None;
+        let mut attributes = None;
+
+        for y in 0..self.height {
+            let mut x = 0;
+            while x < self.width {
+                let i = y * self.width + x;
+                let cell = self.back[i];
+                let rest = self.back_rest.get(&i).cloned();
+                // Wide characters cover the cells to their right, which are skipped.
+                let width = cell_width(cell.ch, rest.as_ref().map_or("", |rest| &rest[..])).max(1);
+                if self.front[i] == Some(cell) && self.front_rest.get(&i) == rest.as_ref() {
+                    x += width;
+                    continue;
+                }
+                // Overwriting a wide character also blanks the cell it covered.
+                if let Some(old) = self.front[i] {
+                    let old_rest = self.front_rest.get(&i).map_or("", |rest| &rest[..]);
+                    if cell_width(old.ch, old_rest) > 1 && x + 1 < self.width {
+                        self.front[i + 1] = None;
+                    }
+                }
+                self.front[i] = Some(cell);
+                match rest {
+                    Some(ref rest) => self.front_rest.insert(i, rest.clone()),
+                    None => self.front_rest.remove(&i),
+                };
+
+                if pos != Some((x, y)) {
+                    out.extend(terminfo::expand(info.cap(terminfo::CURSOR_ADDRESS), &[y as i32, x as i32]));
+                }
+                if attributes != Some((cell.fg, cell.bg, cell.style, cell.underline)) {
+                    write_attributes(info, &mut out, &cell, self.output_mode);
+                    attributes = Some((cell.fg, cell.bg, cell.style, cell.underline));
+                }
+                if x + width > self.width {
+                    // Like termbox, blank a wide character that doesn't fit.
+                    out.extend(vec![b' '; self.width - x]);
+                } else {
+                    let mut utf8 = [0u8; 4];
+                    out.extend_from_slice(cell.ch.encode_utf8(&mut utf8).as_bytes());
+                    if let Some(rest) = rest {
+                        out.extend_from_slice(rest.as_bytes());
+                    }
+                    for covered in i + 1..i + width {
+                        self.front[covered] = Some(self.back[covered]);
+                        self.front_rest.remove(&covered);
+                    }
+                }
+                // Writing to the last column may or may not wrap, depending on the terminal.
+                pos = if x + width < self.width { Some((x + width, y)) } else { None };
+                x += width;
+            }
+        }
+
+        match self.cursor {
+            Some((x, y)) => {
+                out.extend(terminfo::expand(info.cap(terminfo::CURSOR_ADDRESS), &[y as i32, x as i32]));
+                out.extend_from_slice(info.cap(terminfo::CURSOR_NORMAL));
+            }
+            None => out.extend_from_slice(info.cap(terminfo::CURSOR_INVISIBLE)),
+        }
+        out
+    }
+}
+
+/// A backend that drives the terminal itself, without the termbox C library.
+///
+/// It finds out about the terminal from its terminfo entry (falling back to xterm's sequences
+/// if there is none), puts it into raw mode with termios, only redraws the cells that changed
+/// since the last `present`, and decodes key and mouse input itself. Unlike termbox it has no
+/// global state, so more than one can be open at a time, each on its own terminal.
+pub struct PureBackend {
+    tty: Tty,
+    info: Terminfo,
+    screen: Mutex<Screen>,
+    parser: Mutex<Parser>,
+    input_mode: AtomicUsize,
+    bracketed_paste: AtomicBool,
+    focus_events: AtomicBool,
+
+    // Set while the terminal is given back, by `leave` or a panic hook.
+    restored: Arc<AtomicBool>,
+
+    // Makes `running` true until the backend is dropped, after the terminal has been restored.
+    _running: RunningGuard,
+}
+
+impl PureBackend {
+    /// Take over the controlling terminal of the process.
+    pub fn init() -> Result<PureBackend, InitError> {
+        PureBackend::open("/dev/tty")
+    }
+
+    /// Take over the terminal device at `path`.
+    pub fn open(path: &str) -> Result<PureBackend, InitError> {
+        let info = try!(Terminfo::from_env());
+        let tty = try!(Tty::open(path));
+        let (width, height) = tty.size();
+        let backend = PureBackend {
+            parser: Mutex::new(Parser::new(&info)),
+            tty,
+            info,
+            screen: Mutex::new(Screen::new(width, height)),
+            input_mode: AtomicUsize::new(InputMode::Esc as usize),
+            bracketed_paste: AtomicBool::new(false),
+            focus_events: AtomicBool::new(false),
+            restored: Arc::new(AtomicBool::new(true)),
+            _running: running::run_shared(),
+        };
+        try!(backend.enter().map_err(|_| InitError::FailedToOpenTTy));
+        Ok(backend)
+    }
+
+    fn screen(&self) -> MutexGuard<'_, Screen> {
+        match self.screen.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // Set the terminal up for drawing.
+    fn enter(&self) -> io::Result<()> {
+        try!(self.tty.enter_raw_mode());
+        let mut out = Vec::new();
+        out.extend_from_slice(self.info.cap(terminfo::ENTER_CA_MODE));
+        out.extend_from_slice(self.info.cap(terminfo::KEYPAD_XMIT));
+        out.extend_from_slice(self.info.cap(terminfo::CURSOR_INVISIBLE));
+        out.extend_from_slice(self.info.cap(terminfo::EXIT_ATTRIBUTE_MODE));
+        out.extend_from_slice(self.info.cap(terminfo::CLEAR_SCREEN));
+        out.extend(mouse_sequence(self.input_mode.load(Ordering::SeqCst)));
+        if self.bracketed_paste.load(Ordering::SeqCst) {
+            out.extend_from_slice(ENTER_PASTE);
+        }
+        if self.focus_events.load(Ordering::SeqCst) {
+            out.extend_from_slice(ENTER_FOCUS);
+        }
+        self.screen().invalidate();
+        self.restored.store(false, Ordering::SeqCst);
+        self.tty.write_all(&out)
+    }
+
+    // Put the terminal back the way we found it.
+    fn leave(&self) {
+        if self.restored.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let _ = self.tty.write_all(&self.leave_sequence());
+        self.tty.leave_raw_mode();
+    }
+
+    // What `leave` writes. Turning off modes that were never turned on is harmless, so this
+    // doesn't depend on them.
+    fn leave_sequence(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(EXIT_MOUSE);
+        out.extend_from_slice(EXIT_PASTE);
+        out.extend_from_slice(EXIT_FOCUS);
+        out.extend_from_slice(self.info.cap(terminfo::CURSOR_NORMAL));
+        out.extend_from_slice(self.info.cap(terminfo::EXIT_ATTRIBUTE_MODE));
+        out.extend_from_slice(self.info.cap(terminfo::CLEAR_SCREEN));
+        out.extend_from_slice(self.info.cap(terminfo::EXIT_CA_MODE));
+        out.extend_from_slice(self.info.cap(terminfo::KEYPAD_LOCAL));
+        out
+    }
+
+    // Wait for input, or for one of `fds` to be readable, until `deadline`, or forever if there
+    // is none.
+    fn read_event(&self, deadline: Option<Instant>, raw: bool, fds: &[(RawFd, usize)]) -> EventResult {
+        let mut parser = match self.parser.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let alt = self.input_mode.load(Ordering::SeqCst) & InputMode::Alt as usize != 0;
+        loop {
+            if let Some(ev) = parser.next(raw, alt) {
+                return Ok(ev);
+            }
+            let now = Instant::now();
+            let escape = parser.escape_deadline();
+            if escape.is_some_and(|escape| escape <= now) {
+                if let Some(ev) = parser.flush(raw) {
+                    return Ok(ev);
+                }
+            }
+            // Wake up in time to give up on an escape sequence that was cut short.
+            let until = match (deadline, escape) {
+                (Some(deadline), Some(escape)) => Some(cmp::min(deadline, escape)),
+                (deadline, escape) => deadline.or(escape),
+            };
+            let timeout = until.map(|until| if now < until { until - now } else { Duration::from_secs(0) });
+            match try!(self.tty.wait(timeout, fds).map_err(io_error)) {
+                Ready::Input => match self.tty.read(parser.buffer()) {
+                    // Nothing to read although poll said there was: the terminal is gone.
+                    Ok(0) => return Err(EventError::Unknown(-1)),
+                    Ok(_) => (),
+                    // Something else sharing the terminal got to the input first.
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                    Err(err) => return Err(io_error(err)),
+                },
+                Ready::Woken => {
+                    // Either we were resized, or someone wants us to return.
+                    let (width, height) = self.tty.size();
+                    let mut screen = self.screen();
+                    if (width, height) == (screen.width, screen.height) {
+                        return Ok(Event::NoEvent);
+                    }
+                    screen.resize(width, height);
+                    return Ok(Event::ResizeEvent(width as i32, height as i32));
+                }
+                Ready::Readable(token) => return Ok(Event::Readable(token)),
+                Ready::Timeout => {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Ok(Event::NoEvent);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Append the escape sequences that switch to the attributes of `cell`.
+fn write_attributes(info: &Terminfo, out: &mut Vec<u8>, cell: &Cell, mode: OutputMode) {
+    out.extend_from_slice(info.cap(terminfo::EXIT_ATTRIBUTE_MODE));
+    // Attributes the terminal has no capability for come out empty, and are left out.
+    let attributes = [
+        (RB_BOLD, terminfo::ENTER_BOLD_MODE),
+        (RB_DIM, terminfo::ENTER_DIM_MODE),
+        (RB_ITALIC, terminfo::ENTER_ITALICS_MODE),
+        (RB_UNDERLINE, terminfo::ENTER_UNDERLINE_MODE),
+        (RB_BLINK, terminfo::ENTER_BLINK_MODE),
+        (RB_REVERSE, terminfo::ENTER_REVERSE_MODE),
+        (RB_INVISIBLE, terminfo::ENTER_SECURE_MODE),
+    ];
+    for &(style, cap) in &attributes {
+        if cell.style.contains(style) {
+            out.extend_from_slice(info.cap(cap));
+        }
+    }
+    if cell.style.contains(RB_STRIKETHROUGH) {
+        out.extend_from_slice(info.extended(terminfo::ENTER_STRIKETHROUGH).unwrap_or(b""));
+    }
+    let underlines = [
+        (RB_DOUBLE_UNDERLINE, 2),
+        (RB_CURLY_UNDERLINE, 3),
+        (RB_DOTTED_UNDERLINE, 4),
+        (RB_DASHED_UNDERLINE, 5),
+    ];
+    let underline = underlines.iter().find(|&&(style, _)| cell.style.contains(style));
+    match (underline, info.extended(terminfo::SET_UNDERLINE_STYLE)) {
+        (Some(&(_, kind)), Some(cap)) => out.extend(terminfo::expand(cap, &[kind])),
+        (Some(_), None) => out.extend_from_slice(info.cap(terminfo::ENTER_UNDERLINE_MODE)),
+        (None, _) => (),
+    }
+    // Terminals that can color underlines say so with this capability, but it only takes
+    // 24-bit colors.
+    if let Some(cap) = info.extended(terminfo::SET_UNDERLINE_COLOR) {
+        match cell.underline.nearest(mode) {
+            Color::Default => (),
+            Color::Rgb(r, g, b) => {
+                out.extend(terminfo::expand(cap, &[(r as i32) << 16 | (g as i32) << 8 | b as i32]));
+            }
+            color => out.extend(format!("\x1b[58:5:{}m", color.as_256color()).bytes()),
+        }
+    }
+    // Like termbox, colors are written as plain SGR sequences rather than through setaf and
+    // setab, which not every entry has.
+    for &(color, base) in &[(cell.fg, 30), (cell.bg, 40)] {
+        match (color.nearest(mode), mode) {
+            // Already in place after resetting the attributes.
+            (Color::Default, _) => (),
+
+            (Color::Rgb(r, g, b), _) => {
+                out.extend(format!("\x1b[{};2;{};{};{}m", base + 8, r, g, b).bytes());
+            }
+
+            // 16 color mode
+            (color, OutputMode::Normal) | (color, OutputMode::Current) => {
+                out.extend(format!("\x1b[{}m", base + color.as_16color() - 1).bytes());
+            }
+
+            (color, _) => {
+                out.extend(format!("\x1b[{};5;{}m", base + 8, color.as_256color()).bytes());
+            }
+        }
+    }
+}
+
+// What turns mouse reporting on for the input mode `mode`.
+fn mouse_sequence(mode: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    if mode & MOUSE_BIT != 0 {
+        out.extend_from_slice(ENTER_MOUSE);
+        if mode & MOTION_BIT != 0 {
+            out.extend_from_slice(ENTER_MOTION);
+        }
+    }
+    out
+}
+
+impl Backend for PureBackend {
+    fn width(&self) -> usize {
+        self.screen().width
+    }
+
+    fn height(&self) -> usize {
+        self.screen().height
+    }
+
+    fn clear(&self) {
+        let mut screen = self.screen();
+        for cell in &mut screen.back {
+            *cell = BLANK;
+        }
+        screen.back_rest.clear();
+    }
+
+    fn present(&self) {
+        let out = self.screen().draw(&self.info);
+        let _ = self.tty.write_all(&out);
+    }
+
+    fn set_cursor(&self, x: isize, y: isize) {
+        self.screen().cursor = if x < 0 || y < 0 { None } else { Some((x as usize, y as usize)) };
+    }
+
+    fn set_cell(&self, x: usize, y: usize, cell: Cell) {
+        let mut screen = self.screen();
+        if x < screen.width && y < screen.height {
+            let width = screen.width;
+            screen.back[y * width + x] = cell;
+            screen.back_rest.remove(&(y * width + x));
+        }
+    }
+
+    fn set_cluster(&self, x: usize, y: usize, cell: Cell, rest: &str) {
+        let mut screen = self.screen();
+        if x < screen.width && y < screen.height {
+            let i = y * screen.width + x;
+            screen.back[i] = cell;
+            if rest.is_empty() {
+                screen.back_rest.remove(&i);
+            } else {
+                screen.back_rest.insert(i, rest.to_owned());
+            }
+        }
+    }
+
+    fn get_cell(&self, x: usize, y: usize) -> Option<Cell> {
+        let screen = self.screen();
+        if x < screen.width && y < screen.height {
+            Some(screen.back[y * screen.width + x])
+        } else {
+            None
+        }
+    }
+
+    fn get_cluster(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        let screen = self.screen();
+        if x < screen.width && y < screen.height {
+            let i = y * screen.width + x;
+            Some((screen.back[i], screen.back_rest.get(&i).cloned().unwrap_or_default()))
+        } else {
+            None
+        }
+    }
+
+    fn get_presented(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        let screen = self.screen();
+        if x < screen.width && y < screen.height {
+            let i = y * screen.width + x;
+            screen.front[i].map(|cell| (cell, screen.front_rest.get(&i).cloned().unwrap_or_default()))
+        } else {
+            None
+        }
+    }
+
+    fn poll_event(&self, raw: bool) -> EventResult {
+        self.read_event(None, raw, &[])
+    }
+
+    fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult {
+        self.read_event(Some(Instant::now() + timeout), raw, &[])
+    }
+
+    fn set_input_mode(&self, mode: InputMode) {
+        let mode = mode as usize;
+        if mode == InputMode::Current as usize {
+            return;
+        }
+        let old = self.input_mode.swap(mode, Ordering::SeqCst);
+        let mask = MOUSE_BIT | MOTION_BIT;
+        if old & mask != mode & mask {
+            let mut out = EXIT_MOUSE.to_vec();
+            out.extend(mouse_sequence(mode));
+            let _ = self.tty.write_all(&out);
+        }
+    }
+
+    fn set_output_mode(&self, mode: OutputMode) {
+        if let OutputMode::Current = mode {
+            return;
+        }
+        let mut screen = self.screen();
+        screen.output_mode = mode;
+        screen.invalidate();
+    }
+
+    fn set_bracketed_paste(&self, enabled: bool) {
+        if self.bracketed_paste.swap(enabled, Ordering::SeqCst) != enabled {
+            let _ = self.tty.write_all(if enabled { ENTER_PASTE } else { EXIT_PASTE });
+        }
+    }
+
+    fn set_focus_events(&self, enabled: bool) {
+        if self.focus_events.swap(enabled, Ordering::SeqCst) != enabled {
+            let _ = self.tty.write_all(if enabled { ENTER_FOCUS } else { EXIT_FOCUS });
+        }
+    }
+
+    fn suspend(&self) {
+        self.leave();
+    }
+
+    fn resume(&self) -> Result<(), InitError> {
+        self.enter().map_err(|_| InitError::FailedToOpenTTy)
+    }
+
+    fn wake(&self) {
+        self.tty.wake();
+    }
+
+    fn restorer(&self) -> Option<Box<Fn() + Send + Sync>> {
+        let restored = self.restored.clone();
+        let out = self.leave_sequence();
+        let restore = self.tty.restorer();
+        Some(Box::new(move || {
+            if !restored.swap(true, Ordering::SeqCst) {
+                restore(&out);
+            }
+        }))
+    }
+
+    fn wait_event(&self, timeout: Option<Duration>, raw: bool, fds: &[(RawFd, usize)]) -> EventResult {
+        self.read_event(timeout.map(|timeout| Instant::now() + timeout), raw, fds)
+    }
+}
+
+impl Drop for PureBackend {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Screen, BLANK};
+    use super::terminfo::{self, Terminfo};
+    use {Cell, Color, OutputMode, RB_BOLD, RB_DIM, RB_INVISIBLE, RB_ITALIC, RB_STRIKETHROUGH, RB_UNDERLINE};
+
+    fn put(screen: &mut Screen, x: usize, ch: char) {
+        screen.back[x] = Cell { ch, ..BLANK };
+    }
+
+    fn draw(screen: &mut Screen) -> String {
+        String::from_utf8(screen.draw(&Terminfo::xterm())).unwrap()
+    }
+
+    #[test]
+    fn draws_only_changes() {
+        let mut screen = Screen::new(4, 2);
+        put(&mut screen, 1, 'a');
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[m a  \x1b[2;1H    \x1b[?25l");
+        assert_eq!(draw(&mut screen), "\x1b[?25l");
+
+        put(&mut screen, 2, 'b');
+        screen.back[3] = Cell { ch: 'c', fg: Color::Red, style: RB_BOLD, ..BLANK };
+        screen.cursor = Some((0, 1));
+        assert_eq!(draw(&mut screen), "\x1b[1;3H\x1b(B\x1b[mb\x1b(B\x1b[m\x1b[1m\x1b[31mc\x1b[2;1H\x1b[?12l\x1b[?25h");
+    }
+
+    #[test]
+    fn colors_follow_the_output_mode() {
+        let mut screen = Screen::new(1, 1);
+        screen.back[0] = Cell { ch: 'x', fg: Color::Rgb(255, 0, 0), bg: Color::Byte(21), ..BLANK };
+        screen.output_mode = OutputMode::TrueColor;
+        assert!(draw(&mut screen).contains("\x1b(B\x1b[m\x1b[38;2;255;0;0m\x1b[48;5;21mx"));
+        screen.output_mode = OutputMode::EightBit;
+        screen.invalidate();
+        assert!(draw(&mut screen).contains("\x1b(B\x1b[m\x1b[38;5;196m\x1b[48;5;21mx"));
+        screen.output_mode = OutputMode::Normal;
+        screen.invalidate();
+        assert!(draw(&mut screen).contains("\x1b(B\x1b[m\x1b[31m\x1b[44mx"));
+    }
+
+    #[test]
+    fn styles() {
+        let mut screen = Screen::new(1, 1);
+        screen.back[0] = Cell { ch: 'x', style: RB_ITALIC | RB_DIM | RB_STRIKETHROUGH, ..BLANK };
+        assert!(draw(&mut screen).contains("\x1b(B\x1b[m\x1b[2m\x1b[3m\x1b[9mx"));
+        screen.back[0].style = RB_BOLD | RB_INVISIBLE;
+        assert!(draw(&mut screen).contains("\x1b(B\x1b[m\x1b[1m\x1b[8mx"));
+    }
+
+    #[test]
+    fn underline_colors() {
+        let mut info = Terminfo::xterm();
+        info.set_extended(terminfo::SET_UNDERLINE_COLOR, b"\x1b[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%d%;m");
+        let mut screen = Screen::new(3, 1);
+        screen.output_mode = OutputMode::TrueColor;
+        for (x, &underline) in [Color::Rgb(255, 0, 0), Color::Rgb(0, 0, 255), Color::Rgb(0, 0, 255)].iter().enumerate() {
+            screen.back[x] = Cell { ch: 'x', style: RB_UNDERLINE, underline, ..BLANK };
+        }
+        assert_eq!(String::from_utf8(screen.draw(&info)).unwrap(),
+                   "\x1b[1;1H\x1b(B\x1b[m\x1b[4m\x1b[58:2::255:0:0mx\x1b(B\x1b[m\x1b[4m\x1b[58:2::0:0:255mxx\x1b[?25l");
+    }
+
+    #[test]
+    fn wide_characters() {
+        let mut screen = Screen::new(4, 1);
+        put(&mut screen, 0, '日');
+        put(&mut screen, 2, '本');
+        // The covered cells are skipped, and the cursor ends up where it has to be anyway.
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[m日本\x1b[?25l");
+
+        // Overwriting half of a wide character redraws the other half.
+        put(&mut screen, 2, 'a');
+        assert_eq!(draw(&mut screen), "\x1b[1;3H\x1b(B\x1b[ma \x1b[?25l");
+        put(&mut screen, 0, 'b');
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[mb \x1b[?25l");
+    }
+
+    #[test]
+    fn wide_character_at_the_edge() {
+        let mut screen = Screen::new(3, 1);
+        put(&mut screen, 0, 'a');
+        put(&mut screen, 1, 'b');
+        put(&mut screen, 2, '日');
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[mab \x1b[?25l");
+    }
+
+    #[test]
+    fn clusters() {
+        let mut screen = Screen::new(3, 1);
+        put(&mut screen, 0, 'e');
+        screen.back_rest.insert(0, "\u{301}".to_owned());
+        put(&mut screen, 1, '👨');
+        screen.back_rest.insert(1, "\u{200d}👩".to_owned());
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[me\u{301}👨\u{200d}👩\x1b[?25l");
+
+        // Only the rest of the cluster changed.
+        screen.back_rest.remove(&0);
+        assert_eq!(draw(&mut screen), "\x1b[1;1H\x1b(B\x1b[me\x1b[?25l");
+    }
+}
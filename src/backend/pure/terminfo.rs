@@ -0,0 +1,625 @@
+//! Just enough terminfo to drive a terminal: finding and parsing compiled entries, and expanding
+//! parameterized capability strings.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use InitError;
+
+// Indices of the standard string capabilities we use, as laid out in compiled entries.
+pub const CURSOR_ADDRESS: usize = 10;
+pub const CLEAR_SCREEN: usize = 5;
+pub const CURSOR_INVISIBLE: usize = 13;
+pub const CURSOR_NORMAL: usize = 16;
+pub const ENTER_BLINK_MODE: usize = 26;
+pub const ENTER_BOLD_MODE: usize = 27;
+pub const ENTER_CA_MODE: usize = 28;
+pub const ENTER_DIM_MODE: usize = 30;
+pub const ENTER_SECURE_MODE: usize = 32;
+pub const ENTER_REVERSE_MODE: usize = 34;
+pub const ENTER_UNDERLINE_MODE: usize = 36;
+pub const EXIT_ATTRIBUTE_MODE: usize = 39;
+pub const EXIT_CA_MODE: usize = 40;
+pub const KEYPAD_LOCAL: usize = 88;
+pub const KEYPAD_XMIT: usize = 89;
+pub const ENTER_ITALICS_MODE: usize = 311;
+
+// Names of the extended (user-defined) string capabilities we use.
+pub const ENTER_STRIKETHROUGH: &str = "smxx";
+pub const SET_UNDERLINE_STYLE: &str = "Smulx";
+pub const SET_UNDERLINE_COLOR: &str = "Setulc";
+
+/// Capabilities of the keys termbox knows about, in the order of their key codes: the first one
+/// is `0xFFFF` (F1), the next `0xFFFE`, and so on.
+pub const KEYS: [usize; 22] = [
+    66, 68, 69, 70, 71, 72, 73, 74, 75, 67, 216, 217, // F1 - F12
+    77, 59, 76, 164, 82, 81,                          // Insert, Delete, Home, End, PgUp, PgDn
+    87, 61, 79, 83,                                   // Up, Down, Left, Right
+];
+
+const MAGIC_16BIT: u16 = 0o432;
+const MAGIC_32BIT: u16 = 0o1036;
+
+pub struct Terminfo {
+    strings: Vec<Option<Vec<u8>>>,
+    extended: HashMap<String, Vec<u8>>,
+}
+
+impl Terminfo {
+    /// Load the entry for `$TERM`, falling back to built-in xterm capabilities if there is no
+    /// entry for it.
+    pub fn from_env() -> Result<Terminfo, InitError> {
+        let term = match env::var("TERM") {
+            Ok(ref term) if !term.is_empty() => term.clone(),
+            _ => return Err(InitError::UnsupportedTerminal),
+        };
+        for path in search_paths(&term) {
+            let mut data = Vec::new();
+            if let Ok(mut file) = File::open(&path) {
+                if file.read_to_end(&mut data).is_ok() {
+                    if let Some(info) = Terminfo::parse(&data) {
+                        return Ok(info);
+                    }
+                }
+            }
+        }
+        Ok(Terminfo::xterm())
+    }
+
+    /// Parse a compiled terminfo entry.
+    pub fn parse(data: &[u8]) -> Option<Terminfo> {
+        let mut r = Reader { data, pos: 0 };
+        let magic = r.u16()?;
+        let num_size = match magic {
+            MAGIC_16BIT => 2,
+            MAGIC_32BIT => 4,
+            _ => return None,
+        };
+        let names_size = r.count()?;
+        let bool_count = r.count()?;
+        let num_count = r.count()?;
+        let str_count = r.count()?;
+        let table_size = r.count()?;
+
+        r.skip(names_size + bool_count)?;
+        r.align();
+        r.skip(num_count * num_size)?;
+        let mut offsets = Vec::with_capacity(str_count);
+        for _ in 0..str_count {
+            offsets.push(r.i16()?);
+        }
+        let table = r.take(table_size)?;
+        let strings = offsets.iter().map(|&off| string_at(table, off)).collect();
+        // Older entries have no extended section, and a broken one is no reason to give up on
+        // the standard capabilities.
+        r.align();
+        let extended = parse_extended(&mut r, num_size).unwrap_or_default();
+
+        Some(Terminfo { strings, extended })
+    }
+
+
+    /// Built-in capabilities for terminals we can't find an entry for.
+    pub fn xterm() -> Terminfo {
+        let caps: &[(usize, &str)] = &[
+            (CURSOR_ADDRESS, "\x1b[%i%p1%d;%p2%dH"),
+            (CLEAR_SCREEN, "\x1b[H\x1b[2J"),
+            (CURSOR_INVISIBLE, "\x1b[?25l"),
+            (CURSOR_NORMAL, "\x1b[?12l\x1b[?25h"),
+            (ENTER_BLINK_MODE, "\x1b[5m"),
+            (ENTER_BOLD_MODE, "\x1b[1m"),
+            (ENTER_CA_MODE, "\x1b[?1049h"),
+            (ENTER_DIM_MODE, "\x1b[2m"),
+            (ENTER_ITALICS_MODE, "\x1b[3m"),
+            (ENTER_SECURE_MODE, "\x1b[8m"),
+            (ENTER_REVERSE_MODE, "\x1b[7m"),
+            (ENTER_UNDERLINE_MODE, "\x1b[4m"),
+            (EXIT_ATTRIBUTE_MODE, "\x1b(B\x1b[m"),
+            (EXIT_CA_MODE, "\x1b[?1049l"),
+            (KEYPAD_LOCAL, "\x1b[?1l\x1b>"),
+            (KEYPAD_XMIT, "\x1b[?1h\x1b="),
+        ];
+        let keys = [
+            "\x1bOP", "\x1bOQ", "\x1bOR", "\x1bOS", "\x1b[15~", "\x1b[17~", "\x1b[18~", "\x1b[19~",
+            "\x1b[20~", "\x1b[21~", "\x1b[23~", "\x1b[24~",
+            "\x1b[2~", "\x1b[3~", "\x1bOH", "\x1bOF", "\x1b[5~", "\x1b[6~",
+            "\x1bOA", "\x1bOB", "\x1bOD", "\x1bOC",
+        ];
+
+        let extended: &[(&str, &str)] = &[
+            (ENTER_STRIKETHROUGH, "\x1b[9m"),
+        ];
+
+        let mut strings = vec![None; ENTER_ITALICS_MODE + 1];
+        for &(cap, value) in caps {
+            strings[cap] = Some(value.as_bytes().to_vec());
+        }
+        for (&cap, value) in KEYS.iter().zip(keys.iter()) {
+            strings[cap] = Some(value.as_bytes().to_vec());
+        }
+        let extended = extended.iter()
+            .map(|&(name, value)| (name.to_owned(), value.as_bytes().to_vec()))
+            .collect();
+        Terminfo { strings, extended }
+    }
+
+    /// A standard string capability, by index.
+    pub fn string(&self, cap: usize) -> Option<&[u8]> {
+        self.strings.get(cap).and_then(|s| s.as_ref()).map(|s| &s[..])
+    }
+
+    /// A standard string capability, or an empty one if the terminal doesn't have it.
+    pub fn cap(&self, cap: usize) -> &[u8] {
+        self.string(cap).unwrap_or(b"")
+    }
+
+    /// An extended string capability, by name.
+    pub fn extended(&self, name: &str) -> Option<&[u8]> {
+        self.extended.get(name).map(|s| &s[..])
+    }
+
+    #[cfg(test)]
+    pub fn set_extended(&mut self, name: &str, value: &[u8]) {
+        self.extended.insert(name.to_owned(), value.to_vec());
+    }
+}
+
+// Entries live in `<dir>/<first letter>/<name>`, or `<dir>/<first letter in hex>/<name>` on
+// some systems. A name that could lead out of those directories has no entry.
+fn search_paths(term: &str) -> Vec<PathBuf> {
+    if term.is_empty() || term.contains('/') || term.contains("..") {
+        return Vec::new();
+    }
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        for dir in list.split(':') {
+            dirs.push(PathBuf::from(if dir.is_empty() { "/usr/share/terminfo" } else { dir }));
+        }
+    }
+    for dir in &["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"] {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    let first = term.as_bytes()[0];
+    let mut paths = Vec::new();
+    for dir in dirs {
+        paths.push(dir.join((first as char).to_string()).join(term));
+        paths.push(dir.join(format!("{:x}", first)).join(term));
+    }
+    paths
+}
+
+// The extended string capabilities that follow the standard ones in newer entries: counts, then
+// booleans and numbers, the offsets of the values, the offsets of the names of every extended
+// capability, and a table with the values followed by the names.
+fn parse_extended(r: &mut Reader, num_size: usize) -> Option<HashMap<String, Vec<u8>>> {
+    let bool_count = r.count()?;
+    let num_count = r.count()?;
+    let str_count = r.count()?;
+    let _offset_count = r.count()?;
+    let table_size = r.count()?;
+
+    r.skip(bool_count)?;
+    r.align();
+    r.skip(num_count * num_size)?;
+    let mut values = Vec::with_capacity(str_count);
+    for _ in 0..str_count {
+        values.push(r.i16()?);
+    }
+    let mut names = Vec::with_capacity(bool_count + num_count + str_count);
+    for _ in 0..bool_count + num_count + str_count {
+        names.push(r.i16()?);
+    }
+    let table = r.take(table_size)?;
+
+    // Name offsets count from the end of the last value.
+    let names_start = values.iter()
+        .filter_map(|&off| string_at(table, off).map(|value| off as usize + value.len() + 1))
+        .max()
+        .unwrap_or(0);
+    let name_table = table.get(names_start..)?;
+    let extended = names[bool_count + num_count..].iter().zip(&values)
+        .filter_map(|(&name, &value)| {
+            let name = String::from_utf8(string_at(name_table, name)?).ok()?;
+            Some((name, string_at(table, value)?))
+        })
+        .collect();
+    Some(extended)
+}
+
+fn string_at(table: &[u8], off: i16) -> Option<Vec<u8>> {
+    if off < 0 {
+        return None;
+    }
+    let rest = table.get(off as usize..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    Some(rest[..len].to_vec())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn align(&mut self) {
+        if self.pos % 2 == 1 {
+            self.pos += 1;
+        }
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        self.take(2).map(|b| (b[0] as u16 | (b[1] as u16) << 8) as i16)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.i16().map(|n| n as u16)
+    }
+
+    // Counts are stored as signed shorts, with -1 meaning zero.
+    fn count(&mut self) -> Option<usize> {
+        self.i16().map(|n| if n < 0 { 0 } else { n as usize })
+    }
+}
+
+/// Expand a parameterized capability string such as `cup` with the given numeric parameters.
+///
+/// This implements the whole `tparm` language apart from string parameters, which no
+/// capability we use takes.
+pub fn expand(cap: &[u8], params: &[i32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut params: Vec<i32> = params.to_vec();
+    params.resize(9, 0);
+    let mut stack: Vec<i32> = Vec::new();
+    let mut vars = [0i32; 26];
+    let mut i = 0;
+
+    macro_rules! pop { () => { stack.pop().unwrap_or(0) } }
+
+    while i < cap.len() {
+        let c = cap[i];
+        i += 1;
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+        let op = match cap.get(i) {
+            Some(&op) => op,
+            None => break,
+        };
+        i += 1;
+        match op {
+            b'%' => out.push(b'%'),
+            b'c' => out.push(pop!() as u8),
+            b'p' => {
+                let n = cap.get(i).map_or(0, |&d| d.wrapping_sub(b'1') as usize);
+                i += 1;
+                stack.push(params.get(n).cloned().unwrap_or(0));
+            }
+            b'P' => {
+                let v = pop!();
+                if let Some(&name) = cap.get(i) {
+                    if name.is_ascii_alphabetic() {
+                        vars[(name.to_ascii_lowercase() - b'a') as usize] = v;
+                    }
+                }
+                i += 1;
+            }
+            b'g' => {
+                let v = match cap.get(i) {
+                    Some(&name) if name.is_ascii_alphabetic() => vars[(name.to_ascii_lowercase() - b'a') as usize],
+                    _ => 0,
+                };
+                i += 1;
+                stack.push(v);
+            }
+            b'\'' => {
+                stack.push(cap.get(i).cloned().unwrap_or(0) as i32);
+                i += 2;
+            }
+            b'{' => {
+                let mut n = 0i32;
+                while let Some(&d) = cap.get(i) {
+                    i += 1;
+                    if d == b'}' {
+                        break;
+                    }
+                    n = n.wrapping_mul(10).wrapping_add((d as i32) - (b'0' as i32));
+                }
+                stack.push(n);
+            }
+            b'l' => {
+                let v = pop!();
+                stack.push(v.to_string().len() as i32);
+            }
+            b'+' | b'-' | b'*' | b'/' | b'm' | b'&' | b'|' | b'^' | b'=' | b'>' | b'<' | b'A' | b'O' => {
+                let b = pop!();
+                let a = pop!();
+                stack.push(match op {
+                    b'+' => a.wrapping_add(b),
+                    b'-' => a.wrapping_sub(b),
+                    b'*' => a.wrapping_mul(b),
+                    b'/' => if b == 0 { 0 } else { a / b },
+                    b'm' => if b == 0 { 0 } else { a % b },
+                    b'&' => a & b,
+                    b'|' => a | b,
+                    b'^' => a ^ b,
+                    b'=' => (a == b) as i32,
+                    b'>' => (a > b) as i32,
+                    b'<' => (a < b) as i32,
+                    b'A' => (a != 0 && b != 0) as i32,
+                    _ => (a != 0 || b != 0) as i32,
+                });
+            }
+            b'!' => {
+                let v = pop!();
+                stack.push((v == 0) as i32);
+            }
+            b'~' => {
+                let v = pop!();
+                stack.push(!v);
+            }
+            b'i' => {
+                params[0] += 1;
+                params[1] += 1;
+            }
+            b'?' | b';' => (),
+            b't' => {
+                if pop!() == 0 {
+                    i = skip_branch(cap, i, true);
+                }
+            }
+            b'e' => {
+                i = skip_branch(cap, i, false);
+            }
+            _ => {
+                // A printf-style conversion: %[[:]flags][width[.precision]][doxXs]
+                let start = i - 1;
+                let mut j = start;
+                if cap.get(j) == Some(&b':') {
+                    j += 1;
+                }
+                let mut left = false;
+                let mut zero = false;
+                while let Some(&f) = cap.get(j) {
+                    match f {
+                        b'-' => left = true,
+                        b'0' => zero = true,
+                        b'+' | b'#' | b' ' => (),
+                        _ => break,
+                    }
+                    j += 1;
+                }
+                let mut width = 0;
+                while let Some(&d) = cap.get(j) {
+                    if !d.is_ascii_digit() {
+                        break;
+                    }
+                    width = width * 10 + (d - b'0') as usize;
+                    j += 1;
+                }
+                let mut precision = 0;
+                if cap.get(j) == Some(&b'.') {
+                    j += 1;
+                    while let Some(&d) = cap.get(j) {
+                        if !d.is_ascii_digit() {
+                            break;
+                        }
+                        precision = precision * 10 + (d - b'0') as usize;
+                        j += 1;
+                    }
+                }
+                let v = pop!();
+                let digits = match cap.get(j) {
+                    Some(&b'd') | Some(&b's') => v.to_string(),
+                    Some(&b'o') => format!("{:o}", v),
+                    Some(&b'x') => format!("{:x}", v),
+                    Some(&b'X') => format!("{:X}", v),
+                    _ => {
+                        // Not a conversion after all; drop the sequence, but not the value.
+                        stack.push(v);
+                        continue;
+                    }
+                };
+                i = j + 1;
+                let mut digits = digits;
+                while digits.len() < precision {
+                    digits.insert(0, '0');
+                }
+                let pad = width.saturating_sub(digits.len());
+                let fill = if zero && !left { b'0' } else { b' ' };
+                if left {
+                    out.extend_from_slice(digits.as_bytes());
+                }
+                for _ in 0..pad {
+                    out.push(fill);
+                }
+                if !left {
+                    out.extend_from_slice(digits.as_bytes());
+                }
+            }
+        }
+    }
+    out
+}
+
+// Skip to the part of a conditional that runs next: after the matching `%e` or `%;` when the
+// condition was false, or after the matching `%;` when leaving a then-part.
+fn skip_branch(cap: &[u8], mut i: usize, stop_at_else: bool) -> usize {
+    let mut depth = 0;
+    while i + 1 < cap.len() {
+        if cap[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        let op = cap[i + 1];
+        i += 2;
+        match op {
+            b'?' => depth += 1,
+            b';' if depth == 0 => return i,
+            b';' => depth -= 1,
+            b'e' if depth == 0 && stop_at_else => return i,
+            _ => (),
+        }
+    }
+    cap.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_i16(data: &mut Vec<u8>, n: i16) {
+        data.push(n as u8);
+        data.push((n >> 8) as u8);
+    }
+
+    fn pad(data: &mut Vec<u8>) {
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+
+    // A compiled entry in the 16-bit format with the given standard and extended strings, and
+    // no booleans or numbers.
+    fn entry(strings: &[(usize, &str)], extended: &[(&str, &str)]) -> Vec<u8> {
+        let count = strings.iter().map(|&(cap, _)| cap + 1).max().unwrap_or(0);
+        let mut offsets = vec![-1i16; count];
+        let mut table = Vec::new();
+        for &(cap, value) in strings {
+            offsets[cap] = table.len() as i16;
+            table.extend_from_slice(value.as_bytes());
+            table.push(0);
+        }
+        let names = b"test|a terminal for testing\0";
+
+        let mut data = Vec::new();
+        for &n in &[MAGIC_16BIT as i16, names.len() as i16, 0, 0, count as i16, table.len() as i16] {
+            push_i16(&mut data, n);
+        }
+        data.extend_from_slice(names);
+        pad(&mut data);
+        for &off in &offsets {
+            push_i16(&mut data, off);
+        }
+        data.extend_from_slice(&table);
+        if extended.is_empty() {
+            return data;
+        }
+
+        pad(&mut data);
+        let mut table = Vec::new();
+        let mut values = Vec::new();
+        for &(_, value) in extended {
+            values.push(table.len() as i16);
+            table.extend_from_slice(value.as_bytes());
+            table.push(0);
+        }
+        let names_start = table.len();
+        let mut names = Vec::new();
+        for &(name, _) in extended {
+            names.push((table.len() - names_start) as i16);
+            table.extend_from_slice(name.as_bytes());
+            table.push(0);
+        }
+        let n = extended.len() as i16;
+        for &n in &[0, 0, n, 2 * n, table.len() as i16] {
+            push_i16(&mut data, n);
+        }
+        for &off in values.iter().chain(&names) {
+            push_i16(&mut data, off);
+        }
+        data.extend_from_slice(&table);
+        data
+    }
+
+    #[test]
+    fn parse_strings() {
+        let data = entry(&[(CLEAR_SCREEN, "\x1b[H\x1b[2J"), (CURSOR_ADDRESS, "\x1b[%i%p1%d;%p2%dH")], &[]);
+        let info = Terminfo::parse(&data).unwrap();
+        assert_eq!(info.string(CLEAR_SCREEN), Some(&b"\x1b[H\x1b[2J"[..]));
+        assert_eq!(info.string(CURSOR_ADDRESS), Some(&b"\x1b[%i%p1%d;%p2%dH"[..]));
+        assert_eq!(info.string(ENTER_BOLD_MODE), None);
+        assert_eq!(info.cap(ENTER_BOLD_MODE), b"");
+        assert_eq!(info.extended(ENTER_STRIKETHROUGH), None);
+    }
+
+    #[test]
+    fn parse_extended_strings() {
+        let data = entry(&[(ENTER_BOLD_MODE, "\x1b[1m")], &[
+            (ENTER_STRIKETHROUGH, "\x1b[9m"),
+            (SET_UNDERLINE_STYLE, "\x1b[4:%p1%dm"),
+        ]);
+        let info = Terminfo::parse(&data).unwrap();
+        assert_eq!(info.string(ENTER_BOLD_MODE), Some(&b"\x1b[1m"[..]));
+        assert_eq!(info.extended(ENTER_STRIKETHROUGH), Some(&b"\x1b[9m"[..]));
+        assert_eq!(info.extended(SET_UNDERLINE_STYLE), Some(&b"\x1b[4:%p1%dm"[..]));
+        assert_eq!(info.extended(SET_UNDERLINE_COLOR), None);
+    }
+
+    #[test]
+    fn parse_broken_entries() {
+        let data = entry(&[(ENTER_BOLD_MODE, "\x1b[1m")], &[(ENTER_STRIKETHROUGH, "\x1b[9m")]);
+        assert!(Terminfo::parse(&data[..20]).is_none());
+        assert!(Terminfo::parse(b"not terminfo").is_none());
+        // A cut off extended section loses only the extended capabilities.
+        let info = Terminfo::parse(&data[..data.len() - 4]).unwrap();
+        assert_eq!(info.string(ENTER_BOLD_MODE), Some(&b"\x1b[1m"[..]));
+        assert_eq!(info.extended(ENTER_STRIKETHROUGH), None);
+    }
+
+    #[test]
+    fn expand_cursor_address() {
+        assert_eq!(expand(b"\x1b[%i%p1%d;%p2%dH", &[5, 10]), b"\x1b[6;11H");
+        assert_eq!(expand(b"\x1b[%p1%d;%p2%dH", &[0, 0]), b"\x1b[0;0H");
+    }
+
+    #[test]
+    fn expand_conditionals_and_arithmetic() {
+        // setaf from xterm-256color.
+        let setaf = b"\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m";
+        assert_eq!(expand(setaf, &[1]), b"\x1b[31m");
+        assert_eq!(expand(setaf, &[9]), b"\x1b[91m");
+        assert_eq!(expand(setaf, &[200]), b"\x1b[38;5;200m");
+        // Setulc from kitty.
+        let setulc = b"\x1b[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%d%;m";
+        assert_eq!(expand(setulc, &[0x10_20_30]), b"\x1b[58:2::16:32:48m");
+    }
+
+    #[test]
+    fn expand_formats_and_variables() {
+        assert_eq!(expand(b"%p1%03d|%p1%x|%p1%X|%p1%o|%p1%:-4d|", &[42]), b"042|2a|2A|52|42  |");
+        assert_eq!(expand(b"%p1%Pa%ga%ga%+%d", &[21]), b"42");
+        assert_eq!(expand(b"%'A'%c%{66}%c%%", &[]), b"AB%");
+        assert_eq!(expand(b"%p1%!%d %p1%~%d %p1%l%d", &[123]), b"0 -124 3");
+        // What isn't a conversion leaves the stack alone.
+        assert_eq!(expand(b"%p1%z%d", &[7]), b"7");
+    }
+
+    #[test]
+    fn search_paths_stay_in_the_terminfo_directories() {
+        assert!(!search_paths("xterm").is_empty());
+        assert!(search_paths("../../etc/passwd").is_empty());
+        assert!(search_paths("/tmp/evil").is_empty());
+        assert!(search_paths("..").is_empty());
+    }
+}
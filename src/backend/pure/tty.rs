@@ -0,0 +1,253 @@
+//! The terminal device itself: raw mode, window size and waiting for input.
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use libc;
+
+use InitError;
+
+// Write ends of the wakeup pipes of every open terminal, poked by the SIGWINCH handler. A signal
+// handler can't take locks, so this is a fixed set of slots with -1 meaning free.
+static WINCH_FDS: [AtomicI32; 8] = [
+    AtomicI32::new(-1), AtomicI32::new(-1), AtomicI32::new(-1), AtomicI32::new(-1),
+    AtomicI32::new(-1), AtomicI32::new(-1), AtomicI32::new(-1), AtomicI32::new(-1),
+];
+
+// How many terminals are open, and what SIGWINCH did before the first of them.
+static WINCH_INSTALLED: Mutex<(usize, Option<libc::sigaction>)> = Mutex::new((0, None));
+
+fn winch_installed() -> MutexGuard<'static, (usize, Option<libc::sigaction>)> {
+    match WINCH_INSTALLED.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// Catch SIGWINCH while the first terminal is open.
+fn catch_winch() {
+    let mut installed = winch_installed();
+    if installed.0 == 0 {
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = on_winch as extern "C" fn(c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = mem::zeroed();
+            libc::sigaction(libc::SIGWINCH, &action, &mut previous);
+            installed.1 = Some(previous);
+        }
+    }
+    installed.0 += 1;
+}
+
+// Put back what SIGWINCH did before, once the last terminal is closed.
+fn release_winch() {
+    let mut installed = winch_installed();
+    installed.0 -= 1;
+    if installed.0 == 0 {
+        if let Some(previous) = installed.1.take() {
+            unsafe { libc::sigaction(libc::SIGWINCH, &previous, ptr::null_mut()) };
+        }
+    }
+}
+
+extern "C" fn on_winch(_: c_int) {
+    wake_all();
+}
+
+/// Make the `wait` of every open terminal return `Ready::Woken`. Safe to call from a signal
+/// handler.
+pub fn wake_all() {
+    for slot in WINCH_FDS.iter() {
+        let fd = slot.load(Ordering::SeqCst);
+        if fd >= 0 {
+            unsafe { libc::write(fd, b"w".as_ptr() as *const libc::c_void, 1) };
+        }
+    }
+}
+
+pub struct Tty {
+    fd: RawFd,
+    original: libc::termios,
+
+    // Written to by SIGWINCH and `wake`, so that `wait` returns.
+    wake_read: RawFd,
+    wake_write: RawFd,
+}
+
+/// What ended a call to `Tty::wait`.
+#[derive(PartialEq, Debug)]
+pub enum Ready {
+    Input,
+    Woken,
+    Timeout,
+    /// One of the extra file descriptors passed to `wait`, by its token.
+    Readable(usize),
+}
+
+impl Tty {
+    pub fn open(path: &str) -> Result<Tty, InitError> {
+        let path = try!(CString::new(path).map_err(|_| InitError::FailedToOpenTTy));
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(InitError::FailedToOpenTTy);
+        }
+
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            unsafe { libc::close(fd) };
+            return Err(InitError::FailedToOpenTTy);
+        }
+
+        let mut pipe = [0 as c_int; 2];
+        if unsafe { libc::pipe(pipe.as_mut_ptr()) } != 0 {
+            unsafe { libc::close(fd) };
+            return Err(InitError::PipeTrapError);
+        }
+        for &end in &pipe {
+            unsafe {
+                libc::fcntl(end, libc::F_SETFL, libc::O_NONBLOCK);
+                libc::fcntl(end, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+
+        // Dropping the `Tty` undoes all of this, also when a step fails.
+        let tty = Tty { fd, original, wake_read: pipe[0], wake_write: pipe[1] };
+        catch_winch();
+        if !WINCH_FDS.iter().any(|slot| slot.compare_exchange(-1, tty.wake_write, Ordering::SeqCst, Ordering::SeqCst).is_ok()) {
+            return Err(InitError::PipeTrapError);
+        }
+        Ok(tty)
+    }
+
+    /// Put the terminal into raw mode.
+    pub fn enter_raw_mode(&self) -> io::Result<()> {
+        let mut raw = self.original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Restore the terminal settings we found when opening it.
+    pub fn leave_raw_mode(&self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.original) };
+    }
+
+    /// Something that writes a sequence and then restores the terminal settings we found, as
+    /// `leave_raw_mode` does, without needing the `Tty`.
+    pub fn restorer(&self) -> impl Fn(&[u8]) + Send + Sync {
+        let (fd, original) = (self.fd, self.original);
+        move |out| unsafe {
+            libc::write(fd, out.as_ptr() as *const libc::c_void, out.len());
+            libc::tcsetattr(fd, libc::TCSAFLUSH, &original);
+        }
+    }
+
+    /// The size of the terminal as (width, height).
+    pub fn size(&self) -> (usize, usize) {
+        let mut ws: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut ws) } != 0 {
+            return (80, 24);
+        }
+        (ws.ws_col as usize, ws.ws_row as usize)
+    }
+
+    pub fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = unsafe { libc::write(self.fd, buf.as_ptr() as *const libc::c_void, buf.len()) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            buf = &buf[n as usize..];
+        }
+        Ok(())
+    }
+
+    /// Read whatever input is available, appending it to `buf`.
+    ///
+    /// A signal arriving meanwhile is retried; `ErrorKind::WouldBlock` means there was nothing
+    /// to read after all.
+    pub fn read(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut chunk = [0u8; 1024];
+        loop {
+            let n = unsafe { libc::read(self.fd, chunk.as_mut_ptr() as *mut libc::c_void, chunk.len()) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            buf.extend_from_slice(&chunk[..n as usize]);
+            return Ok(n as usize);
+        }
+    }
+
+    /// Wait until there is input, `wake` is called, the terminal is resized or one of `extra`
+    /// is ready to be read, or until `timeout` has elapsed if one is given.
+    pub fn wait(&self, timeout: Option<Duration>, extra: &[(RawFd, usize)]) -> io::Result<Ready> {
+        let mut fds = vec![
+            libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: self.wake_read, events: libc::POLLIN, revents: 0 },
+        ];
+        fds.extend(extra.iter().map(|&(fd, _)| libc::pollfd { fd, events: libc::POLLIN, revents: 0 }));
+        let timeout = match timeout {
+            Some(t) => (t.as_secs() * 1000 + t.subsec_nanos() as u64 / 1000000) as c_int,
+            None => -1,
+        };
+        let n = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            // Most likely SIGWINCH, whose handler has poked the pipe.
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Ready::Woken);
+            }
+            return Err(err);
+        }
+        if fds[1].revents != 0 {
+            let mut drain = [0u8; 64];
+            while unsafe { libc::read(self.wake_read, drain.as_mut_ptr() as *mut libc::c_void, drain.len()) } > 0 {}
+            return Ok(Ready::Woken);
+        }
+        if fds[0].revents != 0 {
+            return Ok(Ready::Input);
+        }
+        if let Some((_, &(_, token))) = fds[2..].iter().zip(extra).find(|&(fd, _)| fd.revents != 0) {
+            return Ok(Ready::Readable(token));
+        }
+        Ok(Ready::Timeout)
+    }
+
+    /// Make a concurrent `wait` return `Ready::Woken`.
+    pub fn wake(&self) {
+        unsafe { libc::write(self.wake_write, b"w".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        for slot in WINCH_FDS.iter() {
+            let _ = slot.compare_exchange(self.wake_write, -1, Ordering::SeqCst, Ordering::SeqCst);
+        }
+        release_winch();
+        unsafe {
+            libc::close(self.wake_read);
+            libc::close(self.wake_write);
+            libc::close(self.fd);
+        }
+    }
+}
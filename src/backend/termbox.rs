@@ -0,0 +1,525 @@
+use std::char;
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use libc;
+use num_traits::FromPrimitive;
+use termbox as sys;
+use termbox::{RawCell, RawEvent};
+
+use super::{io_error, key_event, Backend};
+use palette;
+use running::{self, RunningGuard};
+use style::{RB_ANY_UNDERLINE, TB_ATTRIB, TB_NORMAL_COLOR};
+use {Button, Cell, Color, Event, EventError, EventResult, InitError, InputMode, Mouse, OutputMode, Style, MOD_NONE};
+use RB_UNDERLINE;
+
+const NIL_RAW_EVENT: RawEvent = RawEvent { etype: 0, emod: 0, key: 0, ch: 0, w: 0, h: 0, x: 0, y: 0 };
+
+// Set in `RawEvent::emod` when the mouse moved rather than a button was pressed.
+const TB_MOD_MOTION: u8 = 0x02;
+// The input mode bits termbox knows about.
+const TB_INPUT_MASK: c_int = 0x07;
+
+// The write end of the wakeup pipe of the open `TermboxBackend`, or -1. Written to by `wake` and
+// by the SIGWINCH handler, which can't take locks.
+static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+// The SIGWINCH handler termbox installed, which ours calls before waking us. The whole action
+// is kept to put it back.
+static TERMBOX_WINCH: AtomicUsize = AtomicUsize::new(0);
+static TERMBOX_WINCH_ACTION: Mutex<Option<libc::sigaction>> = Mutex::new(None);
+
+extern "C" fn on_winch(sig: c_int) {
+    let handler = TERMBOX_WINCH.load(Ordering::SeqCst);
+    if handler != libc::SIG_DFL && handler != libc::SIG_IGN {
+        let handler: extern "C" fn(c_int) = unsafe { mem::transmute(handler) };
+        handler(sig);
+    }
+    wake();
+}
+
+/// Make the `wait_event` of the open `TermboxBackend` return, if there is one. Safe to call
+/// from a signal handler.
+pub fn wake() {
+    let fd = WAKE_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        unsafe { libc::write(fd, b"w".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+fn winch_action() -> MutexGuard<'static, Option<libc::sigaction>> {
+    match TERMBOX_WINCH_ACTION.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// Have SIGWINCH wake `wait_event` as well. Termbox installs its handler in `tb_init`, so this is
+// done after every call to it.
+fn chain_winch() {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_winch as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: libc::sigaction = mem::zeroed();
+        libc::sigaction(libc::SIGWINCH, &action, &mut previous);
+        if previous.sa_sigaction != action.sa_sigaction {
+            TERMBOX_WINCH.store(previous.sa_sigaction, Ordering::SeqCst);
+            *winch_action() = Some(previous);
+        }
+    }
+}
+
+// Put termbox's SIGWINCH handler back.
+fn unchain_winch() {
+    if let Some(previous) = winch_action().take() {
+        unsafe { libc::sigaction(libc::SIGWINCH, &previous, ptr::null_mut()) };
+    }
+}
+
+// What `wait_event` waits on besides the registered file descriptors: the terminal, through a
+// descriptor of our own since termbox doesn't share its, and a pipe that `wake` writes to.
+struct WaitFds {
+    tty: RawFd,
+    wake_read: RawFd,
+    wake_write: RawFd,
+}
+
+impl WaitFds {
+    fn open() -> Result<WaitFds, InitError> {
+        let tty = unsafe { libc::open(b"/dev/tty\0".as_ptr() as *const libc::c_char, libc::O_RDONLY | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if tty < 0 {
+            return Err(InitError::FailedToOpenTTy);
+        }
+        let mut pipe = [0 as c_int; 2];
+        if unsafe { libc::pipe(pipe.as_mut_ptr()) } != 0 {
+            unsafe { libc::close(tty) };
+            return Err(InitError::PipeTrapError);
+        }
+        for &end in &pipe {
+            unsafe {
+                libc::fcntl(end, libc::F_SETFL, libc::O_NONBLOCK);
+                libc::fcntl(end, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        Ok(WaitFds { tty, wake_read: pipe[0], wake_write: pipe[1] })
+    }
+}
+
+impl Drop for WaitFds {
+    fn drop(&mut self) {
+        let _ = WAKE_FD.compare_exchange(self.wake_write, -1, Ordering::SeqCst, Ordering::SeqCst);
+        unsafe {
+            libc::close(self.tty);
+            libc::close(self.wake_read);
+            libc::close(self.wake_write);
+        }
+    }
+}
+
+/// Unpack a RawEvent to an Event
+///
+/// See `key_event` for the meaning of `raw`.
+fn unpack_event(ev_type: c_int, ev: &RawEvent, raw: bool) -> EventResult {
+    match ev_type {
+        0 => Ok(Event::NoEvent),
+        1 => Ok(key_event(ev.emod, ev.key, ev.ch, raw)),
+        2 => Ok(Event::ResizeEvent(ev.w, ev.h)),
+        3 => {
+            let mouse = match (Mouse::from_code(ev.key), ev.emod & TB_MOD_MOTION != 0) {
+                (Some(Mouse::Left), true) => Mouse::Drag(Button::Left),
+                (Some(Mouse::Right), true) => Mouse::Drag(Button::Right),
+                (Some(Mouse::Middle), true) => Mouse::Drag(Button::Middle),
+                (Some(Mouse::Release), true) => Mouse::Move,
+                (Some(mouse), _) => mouse,
+                (None, _) => Mouse::Unknown(ev.key),
+            };
+            // Termbox doesn't report modifiers for the mouse.
+            Ok(Event::MouseEvent(mouse, MOD_NONE, ev.x, ev.y))
+        },
+        // `unwrap` is safe here because FromPrimitive for EventError only returns `Some`.
+        n => Err(FromPrimitive::from_isize(n as isize).unwrap()),
+    }
+}
+
+/// Encode a color the way termbox expects it in the given output mode.
+///
+/// Only the 16 color mode has a default color, `default` is used instead in the others.
+fn pack_color(color: Color, default: Color, mode: OutputMode) -> u16 {
+    // 256 color mode is as close as termbox gets to true color.
+    let mode = match mode {
+        OutputMode::TrueColor => OutputMode::EightBit,
+        mode => mode,
+    };
+    let color = match (color.nearest(mode), mode) {
+        (Color::Default, OutputMode::Normal) | (Color::Default, OutputMode::Current) => Color::Default,
+        (Color::Default, _) => default.nearest(mode),
+        (color, _) => color,
+    };
+    match mode {
+        OutputMode::EightBit => color.as_256color(),
+        // Termbox counts from the start of the range these modes are limited to.
+        OutputMode::WebSafe => color.as_256color() - palette::CUBE_START,
+        OutputMode::Grayscale => color.as_256color() - palette::GRAY_START,
+        // 16 color mode
+        _ => Style::from_color(color).bits() as u16,
+    }
+}
+
+/// Encode a cell the way termbox expects it in the given output mode.
+fn pack_cell(cell: &Cell, mode: OutputMode) -> RawCell {
+    // Go with what terminals usually default to.
+    let fg = pack_color(cell.fg, Color::White, mode);
+    let bg = pack_color(cell.bg, Color::Black, mode);
+    // Termbox only has the one kind of underline, and no underline color.
+    let style = if cell.style.intersects(RB_ANY_UNDERLINE) { cell.style | RB_UNDERLINE } else { cell.style };
+    RawCell {
+        ch: cell.ch as u32,
+        fg: fg | (style & TB_ATTRIB).bits() as u16,
+        bg,
+    }
+}
+
+/// Inverse of `pack_cell`.
+fn unpack_cell(raw: &RawCell, mode: OutputMode) -> Cell {
+    let color = |bits: u16| match mode {
+        OutputMode::EightBit | OutputMode::TrueColor => Color::from_256color(bits & 0x00FF),
+        OutputMode::WebSafe => Color::from_256color((bits & 0x00FF) + palette::CUBE_START),
+        OutputMode::Grayscale => Color::from_256color((bits & 0x00FF) + palette::GRAY_START),
+        _ => Color::from_16color(bits & TB_NORMAL_COLOR.bits() as u16),
+    };
+    Cell {
+        // Termbox only ever stores what it was given, so this can only fail if the cell was
+        // written through `change_cell` with an invalid code point.
+        ch: char::from_u32(raw.ch).unwrap_or('\u{fffd}'),
+        fg: color(raw.fg),
+        bg: color(raw.bg),
+        style: (Style::from_bits_truncate(raw.fg as u32) | Style::from_bits_truncate(raw.bg as u32)) & TB_ATTRIB,
+        underline: Color::Default,
+    }
+}
+
+// A copy of termbox's back buffer. Termbox reallocates its own when it takes in a resize, so
+// cells are read from here instead. Like termbox's, it is only touched under the output lock.
+struct Back {
+    width: usize,
+    height: usize,
+    cells: Vec<RawCell>,
+}
+
+/// The default backend, which drives the terminal through the termbox C library.
+///
+/// Termbox keeps its state in globals, so only one `TermboxBackend` can exist at a time. While
+/// it is shut down, by `suspend` or a panic hook, drawing does nothing and reading events fails.
+pub struct TermboxBackend {
+    // Kept to set them again on `resume`, termbox starts over with its defaults.
+    input_mode: Mutex<InputMode>,
+    output_mode: Mutex<OutputMode>,
+
+    // Set while termbox is shut down, by `suspend` or a panic hook. Shutting it down twice
+    // aborts the process, and its buffers are gone until it is started again.
+    shut_down: Arc<AtomicBool>,
+
+    back: Mutex<Back>,
+
+    wait_fds: WaitFds,
+
+    // RAII lock, released when the backend is dropped, after termbox has been shut down.
+    _running: RunningGuard,
+}
+
+impl TermboxBackend {
+    pub fn init() -> Result<TermboxBackend, InitError> {
+        let running = match running::run() {
+            Some(r) => r,
+            None => return Err(InitError::AlreadyOpen),
+        };
+
+        let wait_fds = try!(WaitFds::open());
+        match unsafe { sys::tb_init() } {
+            0 => {
+                WAKE_FD.store(wait_fds.wake_write, Ordering::SeqCst);
+                chain_winch();
+                let backend = TermboxBackend {
+                    input_mode: Mutex::new(InputMode::Current),
+                    output_mode: Mutex::new(OutputMode::Current),
+                    shut_down: Arc::new(AtomicBool::new(false)),
+                    back: Mutex::new(Back { width: 0, height: 0, cells: Vec::new() }),
+                    wait_fds,
+                    _running: running,
+                };
+                backend.sync_back();
+                Ok(backend)
+            }
+            res => Err(FromPrimitive::from_isize(res as isize).unwrap()),
+        }
+    }
+
+    fn input_mode(&self) -> InputMode {
+        match self.input_mode.lock() {
+            Ok(mode) => *mode,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    fn output_mode(&self) -> OutputMode {
+        match self.output_mode.lock() {
+            Ok(mode) => *mode,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    fn back(&self) -> MutexGuard<'_, Back> {
+        match self.back.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // Copy termbox's back buffer, after it may have been cleared or reallocated.
+    fn sync_back(&self) {
+        let (width, height) = (self.width(), self.height());
+        let mut back = self.back();
+        back.width = width;
+        back.height = height;
+        back.cells.clear();
+        if width * height > 0 {
+            back.cells.extend_from_slice(unsafe { slice::from_raw_parts(sys::tb_cell_buffer(), width * height) });
+        }
+    }
+
+    // Write a cell to termbox's back buffer and to the copy of it.
+    pub(crate) fn put_cell(&self, x: usize, y: usize, raw: RawCell) {
+        if !self.active() {
+            return;
+        }
+        unsafe { sys::tb_put_cell(x as c_int, y as c_int, &raw) }
+        let mut back = self.back();
+        if x < back.width && y < back.height {
+            let i = y * back.width + x;
+            back.cells[i] = raw;
+        }
+    }
+
+    // Whether termbox is running, and can be called.
+    fn active(&self) -> bool {
+        !self.shut_down.load(Ordering::SeqCst)
+    }
+}
+
+impl Backend for TermboxBackend {
+    fn width(&self) -> usize {
+        if !self.active() {
+            return 0;
+        }
+        unsafe { sys::tb_width() as usize }
+    }
+
+    fn height(&self) -> usize {
+        if !self.active() {
+            return 0;
+        }
+        unsafe { sys::tb_height() as usize }
+    }
+
+    fn clear(&self) {
+        if self.active() {
+            unsafe { sys::tb_clear() }
+            self.sync_back();
+        }
+    }
+
+    fn present(&self) {
+        if !self.active() {
+            return;
+        }
+        unsafe { sys::tb_present() }
+        // Termbox takes in a resize when presenting, keeping what still fits.
+        let resized = {
+            let back = self.back();
+            (back.width, back.height) != (self.width(), self.height())
+        };
+        if resized {
+            self.sync_back();
+        }
+    }
+
+    fn set_cursor(&self, x: isize, y: isize) {
+        if self.active() {
+            unsafe { sys::tb_set_cursor(x as c_int, y as c_int) }
+        }
+    }
+
+    fn set_cell(&self, x: usize, y: usize, cell: Cell) {
+        self.put_cell(x, y, pack_cell(&cell, self.output_mode()));
+    }
+
+    fn get_cell(&self, x: usize, y: usize) -> Option<Cell> {
+        let back = self.back();
+        if x >= back.width || y >= back.height {
+            return None;
+        }
+        Some(unpack_cell(&back.cells[y * back.width + x], self.output_mode()))
+    }
+
+    fn poll_event(&self, raw: bool) -> EventResult {
+        if !self.active() {
+            return Err(EventError::TermboxError);
+        }
+        let mut ev = NIL_RAW_EVENT;
+        let rc = unsafe {
+            sys::tb_poll_event(&mut ev)
+        };
+        unpack_event(rc, &ev, raw)
+    }
+
+    fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult {
+        if !self.active() {
+            return Err(EventError::TermboxError);
+        }
+        let mut ev = NIL_RAW_EVENT;
+        let rc = unsafe {
+            sys::tb_peek_event(&mut ev, (timeout.as_secs() * 1000 + timeout.subsec_nanos() as u64 / 1000000) as c_int)
+        };
+        unpack_event(rc, &ev, raw)
+    }
+
+    fn set_input_mode(&self, mode: InputMode) {
+        match self.input_mode.lock() {
+            Ok(mut current) => *current = mode,
+            Err(poisoned) => *poisoned.into_inner() = mode,
+        }
+
+        if !self.active() {
+            return;
+        }
+        unsafe {
+            // Termbox only reports motion while a button is held, whatever the mode.
+            sys::tb_select_input_mode(mode as c_int & TB_INPUT_MASK);
+        }
+    }
+
+    fn set_output_mode(&self, mode: OutputMode) {
+        match self.output_mode.lock() {
+            Ok(mut current) => *current = mode,
+            Err(poisoned) => *poisoned.into_inner() = mode,
+        }
+
+        if !self.active() {
+            return;
+        }
+        let mode = match mode {
+            OutputMode::TrueColor => OutputMode::EightBit,
+            mode => mode,
+        };
+        unsafe {
+            sys::tb_select_output_mode(mode as c_int);
+        }
+    }
+
+    fn suspend(&self) {
+        if !self.shut_down.swap(true, Ordering::SeqCst) {
+            unsafe {
+                sys::tb_shutdown();
+            }
+        }
+    }
+
+    fn resume(&self) -> Result<(), InitError> {
+        match unsafe { sys::tb_init() } {
+            0 => self.shut_down.store(false, Ordering::SeqCst),
+            res => return Err(FromPrimitive::from_isize(res as isize).unwrap()),
+        }
+        chain_winch();
+        self.set_input_mode(self.input_mode());
+        self.set_output_mode(self.output_mode());
+        self.sync_back();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        wake();
+    }
+
+    // Termbox only reads the terminal from within `tb_peek_event` and `tb_poll_event`, so this
+    // waits for the terminal together with the wakeup pipe and `fds`, and then has termbox take
+    // whatever came in without waiting.
+    fn wait_event(&self, timeout: Option<Duration>, raw: bool, fds: &[(RawFd, usize)]) -> EventResult {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            // Input termbox has already read, or a resize it has caught.
+            match try!(self.peek_event(Duration::from_secs(0), raw)) {
+                Event::NoEvent => (),
+                ev => return Ok(ev),
+            }
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(Event::NoEvent);
+                    }
+                    // Rounded up, so as not to wake up just before the deadline.
+                    let left = deadline - now;
+                    (left.as_secs() * 1000 + (left.subsec_nanos() as u64).div_ceil(1_000_000)) as c_int
+                }
+                None => -1,
+            };
+            let mut pollfds = vec![
+                libc::pollfd { fd: self.wait_fds.tty, events: libc::POLLIN, revents: 0 },
+                libc::pollfd { fd: self.wait_fds.wake_read, events: libc::POLLIN, revents: 0 },
+            ];
+            pollfds.extend(fds.iter().map(|&(fd, _)| libc::pollfd { fd, events: libc::POLLIN, revents: 0 }));
+            let n = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                // Most likely SIGWINCH, which termbox has caught.
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(io_error(err));
+            }
+            if pollfds[1].revents != 0 {
+                let mut drain = [0u8; 64];
+                while unsafe { libc::read(self.wait_fds.wake_read, drain.as_mut_ptr() as *mut libc::c_void, drain.len()) } > 0 {}
+                return Ok(Event::NoEvent);
+            }
+            if let Some((_, &(_, token))) = pollfds[2..].iter().zip(fds).find(|&(fd, _)| fd.revents != 0) {
+                return Ok(Event::Readable(token));
+            }
+        }
+    }
+
+    fn restorer(&self) -> Option<Box<Fn() + Send + Sync>> {
+        let shut_down = self.shut_down.clone();
+        Some(Box::new(move || {
+            if !shut_down.swap(true, Ordering::SeqCst) {
+                unsafe {
+                    sys::tb_shutdown();
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for TermboxBackend {
+    fn drop(&mut self) {
+        // Since only one instance of the backend is ever accessible, we should not
+        // need to do this atomically.
+        // Note: we should definitely have RUSTBOX_RUNNING = true here.
+        if !self.shut_down.swap(true, Ordering::SeqCst) {
+            unsafe {
+                sys::tb_shutdown();
+            }
+        }
+        unchain_winch();
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::MutexGuard;
+
+use super::{Color, Style};
+use backend::Backend;
+
+/// A single character cell of the back buffer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub style: Style,
+    /// The color of underlines, or `Color::Default` to underline in `fg`.
+    pub underline: Color,
+}
+
+/// A read-only view over the whole back buffer.
+///
+/// The view holds the output lock for as long as it is alive, so nothing can be drawn while it
+/// exists.
+pub struct CellBuffer<'a, B: 'a + Backend> {
+    _lock: MutexGuard<'a, ()>,
+    backend: &'a B,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, B: Backend> CellBuffer<'a, B> {
+    pub(crate) fn new(lock: MutexGuard<'a, ()>, backend: &'a B) -> CellBuffer<'a, B> {
+        CellBuffer {
+            _lock: lock,
+            backend,
+            width: backend.width(),
+            height: backend.height(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the cell at (`x`, `y`), or `None` if it is outside of the buffer.
+    pub fn get(&self, x: usize, y: usize) -> Option<Cell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.backend.get_cell(x, y)
+    }
+
+    /// Iterate over every cell in the buffer, row by row.
+    ///
+    /// ```
+    /// use rustbox::RustBox;
+    ///
+    /// let rb = RustBox::headless(3, 2);
+    /// let buffer = rb.cell_buffer();
+    /// let mut cells = buffer.iter();
+    /// cells.next();
+    /// assert_eq!(cells.size_hint(), (0, Some(5)));
+    /// assert!(cells.all(|cell| cell.ch == ' '));
+    /// ```
+    pub fn iter(&self) -> Iter<'_, 'a, B> {
+        Iter { buffer: self, index: 0 }
+    }
+}
+
+/// Iterator over the cells of a `CellBuffer`, created by `CellBuffer::iter`.
+pub struct Iter<'b, 'a: 'b, B: 'a + Backend> {
+    buffer: &'b CellBuffer<'a, B>,
+    index: usize,
+}
+
+impl<'b, 'a, B: Backend> Iterator for Iter<'b, 'a, B> {
+    type Item = Cell;
+
+    fn next(&mut self) -> Option<Cell> {
+        let width = self.buffer.width;
+        if width == 0 || self.index >= width * self.buffer.height {
+            return None;
+        }
+        let cell = self.buffer.get(self.index % width, self.index / width);
+        // Stop for good if the backend has fewer cells than it said it had.
+        self.index = if cell.is_some() { self.index + 1 } else { width * self.buffer.height };
+        cell
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Only the upper bound is certain, see `next`.
+        let left = (self.buffer.width * self.buffer.height).saturating_sub(self.index);
+        (0, Some(left))
+    }
+}
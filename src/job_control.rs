@@ -0,0 +1,156 @@
+//! Stopping the process like Ctrl-Z would outside of raw mode, see `InitOptions::job_control`.
+
+use std::mem;
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use libc;
+
+// Set by the SIGTSTP handler, until the event loop gets around to stopping.
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+// How many `RustBox`es have job control on, and what SIGTSTP did before the first of them.
+static INSTALLED: Mutex<(usize, Option<libc::sigaction>)> = Mutex::new((0, None));
+
+fn installed() -> MutexGuard<'static, (usize, Option<libc::sigaction>)> {
+    match INSTALLED.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+extern "C" fn on_tstp(_: c_int) {
+    PENDING.store(true, Ordering::SeqCst);
+    ::backend::wake_ttys();
+}
+
+/// Keeps SIGTSTP caught for a `RustBox` with job control on. Once the last one is dropped,
+/// SIGTSTP does whatever it did before again.
+pub struct Guard(());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut installed = installed();
+        installed.0 -= 1;
+        if installed.0 == 0 {
+            if let Some(previous) = installed.1.take() {
+                set_action(&previous);
+            }
+            PENDING.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Catch SIGTSTP, so that the terminal can be given back before stopping.
+pub fn install() -> Guard {
+    let mut installed = installed();
+    if installed.0 == 0 {
+        installed.1 = Some(set_handler(on_tstp as extern "C" fn(c_int) as libc::sighandler_t));
+    }
+    installed.0 += 1;
+    Guard(())
+}
+
+/// Whether a SIGTSTP came in since the last call.
+pub fn take_pending() -> bool {
+    PENDING.swap(false, Ordering::SeqCst)
+}
+
+/// Stop the process, returning once it is continued.
+pub fn stop() {
+    PENDING.store(false, Ordering::SeqCst);
+    // With the default action, so that the shell reports it as it would for Ctrl-Z.
+    let previous = set_handler(libc::SIG_DFL);
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    set_action(&previous);
+}
+
+// Have SIGTSTP call `handler`, as `signal` would, returning what it did before.
+fn set_handler(handler: libc::sighandler_t) -> libc::sigaction {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: libc::sigaction = mem::zeroed();
+        libc::sigaction(libc::SIGTSTP, &action, &mut previous);
+        previous
+    }
+}
+
+fn set_action(action: &libc::sigaction) {
+    unsafe {
+        libc::sigaction(libc::SIGTSTP, action, ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::Ordering;
+
+    use libc;
+
+    use super::{install, on_tstp, set_handler, stop, take_pending, PENDING};
+
+    fn handler() -> libc::sighandler_t {
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            libc::sigaction(libc::SIGTSTP, ptr::null(), &mut action);
+            action.sa_sigaction
+        }
+    }
+
+    // Both in one test, since they share the process' SIGTSTP action.
+    #[test]
+    fn install_and_stop() {
+        let previous = set_handler(libc::SIG_IGN);
+        let ours = on_tstp as extern "C" fn(_) as libc::sighandler_t;
+
+        let first = install();
+        assert_eq!(handler(), ours);
+        let second = install();
+        drop(first);
+        assert_eq!(handler(), ours);
+
+        unsafe {
+            libc::raise(libc::SIGTSTP);
+        }
+        assert!(take_pending());
+        assert!(!take_pending());
+
+        drop(second);
+        assert_eq!(handler(), libc::SIG_IGN);
+
+        // Stopping for real, in a child that is continued again. Only async-signal-safe calls
+        // are made after forking, since other tests may be running on other threads.
+        PENDING.store(true, Ordering::SeqCst);
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                // In a process group of its own, which isn't orphaned, so the stop isn't
+                // discarded.
+                libc::setpgid(0, 0);
+                stop();
+                let restored = handler() == libc::SIG_IGN && !PENDING.load(Ordering::SeqCst);
+                libc::_exit(if restored { 0 } else { 1 });
+            }
+            let mut status = 0;
+            assert_eq!(libc::waitpid(pid, &mut status, libc::WUNTRACED), pid);
+            assert!(libc::WIFSTOPPED(status));
+            assert_eq!(libc::WSTOPSIG(status), libc::SIGTSTP);
+            libc::kill(pid, libc::SIGCONT);
+            assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+            assert!(libc::WIFEXITED(status));
+            assert_eq!(libc::WEXITSTATUS(status), 0);
+        }
+
+        super::set_action(&previous);
+    }
+}
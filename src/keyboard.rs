@@ -1,3 +1,17 @@
+bitflags! {
+    /// Modifier keys held down during a key press.
+    ///
+    /// Control characters keep coming in as `Key::Ctrl`, `MOD_CTRL` is only set for keys that
+    /// terminals report with modifiers attached, such as Ctrl+Right. Which modifiers can be
+    /// detected at all depends on the terminal and the backend; Alt needs `InputMode::Alt`.
+    flags Modifiers: u8 {
+        const MOD_NONE = 0x00,
+        const MOD_ALT = 0x01,
+        const MOD_CTRL = 0x04,
+        const MOD_SHIFT = 0x08,
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Key {
     Tab,
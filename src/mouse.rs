@@ -5,7 +5,24 @@ pub enum Mouse {
     Middle,
     Release,
     WheelUp,
-    WheelDown
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+    /// The mouse moved while `Button` was held down.
+    Drag(Button),
+    /// The mouse moved with no button held down. Only reported in the `*MouseMotion` input
+    /// modes, and not by every backend.
+    Move,
+    /// A mouse event rustbox doesn't know, with the backend's code for it.
+    Unknown(u16),
+}
+
+/// A mouse button, as held down during `Mouse::Drag`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
 }
 
 impl Mouse {
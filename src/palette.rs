@@ -0,0 +1,129 @@
+//! The xterm 256 color palette, and finding the color in it nearest to an arbitrary one.
+
+// The 16 system colors, as xterm shows them by default.
+const SYSTEM: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+// The values each channel takes in the 6x6x6 color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+pub const CUBE_START: u16 = 16;
+pub const GRAY_START: u16 = 232;
+
+/// The color that index `n` of the palette stands for.
+pub fn rgb(n: u8) -> (u8, u8, u8) {
+    let n = n as u16;
+    if n < CUBE_START {
+        SYSTEM[n as usize]
+    } else if n < GRAY_START {
+        let i = n - CUBE_START;
+        (CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize])
+    } else {
+        let v = (8 + 10 * (n - GRAY_START)) as u8;
+        (v, v, v)
+    }
+}
+
+// Index of the nearest cube level, used for each channel.
+fn cube_level(v: u8) -> u16 {
+    match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        v => (v as u16 - 35) / 40,
+    }
+}
+
+/// Nearest color of the 6x6x6 cube, as a palette index.
+pub fn nearest_cube(r: u8, g: u8, b: u8) -> u16 {
+    CUBE_START + 36 * cube_level(r) + 6 * cube_level(g) + cube_level(b)
+}
+
+/// Nearest color of the grayscale ramp, as a palette index.
+pub fn nearest_gray(r: u8, g: u8, b: u8) -> u16 {
+    // Rec. 709 luma, so that e.g. pure blue comes out darker than pure green.
+    let luma = (2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000;
+    let step = (luma.saturating_sub(3) / 10).min(23);
+    GRAY_START + step as u16
+}
+
+/// Nearest of the cube and grayscale colors, as a palette index. The system colors are left out,
+/// since terminals tend to let users change them.
+pub fn nearest_256(r: u8, g: u8, b: u8) -> u16 {
+    let cube = nearest_cube(r, g, b);
+    let gray = nearest_gray(r, g, b);
+    if distance((r, g, b), rgb(gray as u8)) < distance((r, g, b), rgb(cube as u8)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Nearest of the eight basic colors, as a palette index.
+pub fn nearest_8(r: u8, g: u8, b: u8) -> u16 {
+    (0..8).min_by_key(|&n| distance((r, g, b), SYSTEM[n])).unwrap_or(0) as u16
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_colors() {
+        assert_eq!(rgb(0), (0, 0, 0));
+        assert_eq!(rgb(15), (255, 255, 255));
+        assert_eq!(rgb(16), (0, 0, 0));
+        assert_eq!(rgb(67), (95, 135, 175));
+        assert_eq!(rgb(231), (255, 255, 255));
+        assert_eq!(rgb(232), (8, 8, 8));
+        assert_eq!(rgb(255), (238, 238, 238));
+    }
+
+    #[test]
+    fn nearest_cube_colors() {
+        assert_eq!(nearest_cube(0, 0, 0), 16);
+        assert_eq!(nearest_cube(95, 135, 175), 67);
+        assert_eq!(nearest_cube(255, 255, 255), 231);
+        // Where the levels meet.
+        assert_eq!(nearest_cube(47, 48, 114), 16 + 6 + 1);
+        assert_eq!(nearest_cube(115, 154, 155), 16 + 36 * 2 + 6 * 2 + 3);
+    }
+
+    #[test]
+    fn nearest_gray_colors() {
+        assert_eq!(nearest_gray(0, 0, 0), 232);
+        assert_eq!(nearest_gray(8, 8, 8), 232);
+        assert_eq!(nearest_gray(18, 18, 18), 233);
+        assert_eq!(nearest_gray(238, 238, 238), 255);
+        assert_eq!(nearest_gray(255, 255, 255), 255);
+        assert!(nearest_gray(0, 0, 255) < nearest_gray(0, 255, 0));
+    }
+
+    #[test]
+    fn nearest_256_colors() {
+        for &n in &[16, 67, 231, 232, 244, 255] {
+            let (r, g, b) = rgb(n as u8);
+            assert_eq!(nearest_256(r, g, b), n);
+        }
+        // The system colors are never picked, even when they match exactly.
+        assert_eq!(nearest_256(205, 0, 0), 160);
+    }
+
+    #[test]
+    fn nearest_8_colors() {
+        for n in 0..8 {
+            let (r, g, b) = rgb(n as u8);
+            assert_eq!(nearest_8(r, g, b), n);
+        }
+        assert_eq!(nearest_8(255, 255, 255), 7);
+        assert_eq!(nearest_8(30, 30, 30), 0);
+    }
+}
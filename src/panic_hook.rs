@@ -0,0 +1,95 @@
+//! The panic hook installed by `InitOptions::panic_hook`.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+
+use gag::Hold;
+
+use running;
+
+// What the hook has to undo, for the `RustBox` that registered it.
+struct Registration {
+    id: usize,
+    restore: Option<Box<Fn() + Send + Sync>>,
+    stderr: Option<Hold>,
+}
+
+static REGISTRATIONS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static INSTALL: Once = Once::new();
+
+fn registrations() -> MutexGuard<'static, Vec<Registration>> {
+    match REGISTRATIONS.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Keeps the registration of a `RustBox` with the hook. Dropping it flushes the stderr buffer
+/// it holds, so it has to be dropped after the backend.
+pub struct Guard {
+    id: usize,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let removed = {
+            let mut registrations = registrations();
+            registrations.iter().position(|r| r.id == self.id).map(|index| registrations.remove(index))
+        };
+        // Flushes the stderr buffer outside of the lock.
+        drop(removed);
+    }
+}
+
+/// Install the hook, if it isn't already, and have it call `restore` and release `stderr`
+/// before reporting a panic, alongside any other `RustBox` registered with it.
+pub fn register(restore: Option<Box<Fn() + Send + Sync>>, stderr: Option<Hold>) -> Guard {
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !report(info) {
+                previous(info);
+            }
+        }));
+    });
+
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    registrations().push(Registration { id, restore, stderr });
+    Guard { id }
+}
+
+// Restore the terminal and print the panic, if a `RustBox` is registered and still running.
+// Otherwise leave it to the hook that was there before.
+fn report(info: &Display) -> bool {
+    if !running::running() {
+        return false;
+    }
+    let registrations = mem::take(&mut *registrations());
+    if registrations.is_empty() {
+        return false;
+    }
+    // Newest first, so that each one is undone on top of the terminal state it left behind.
+    for registration in registrations.into_iter().rev() {
+        if let Some(ref restore) = registration.restore {
+            restore();
+        }
+        // Dropping the hold writes out what it buffered, ahead of the panic.
+        drop(registration.stderr);
+    }
+
+    // Like the default hook, only when `RUST_BACKTRACE` asks for one.
+    let backtrace = Backtrace::capture();
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let _ = match backtrace.status() {
+        BacktraceStatus::Captured => writeln!(stderr, "\n{}\n\nstack backtrace:\n{}", info, backtrace),
+        _ => writeln!(stderr, "\n{}", info),
+    };
+    true
+}
@@ -0,0 +1,68 @@
+//! Threads that read events on behalf of a `RustBox`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use {Backend, Event, EventResult, Shared};
+
+// How long the thread waits for input at a time before checking whether it should stop, for
+// backends that `wake` can't interrupt.
+const INTERVAL: Duration = Duration::from_millis(50);
+
+/// A thread passing every event read from a backend to a callback, until the callback returns
+/// `false`, an error is read or the `Reader` is stopped or dropped. The callback is dropped when
+/// the thread ends, also if it panics.
+pub struct Reader<B: Backend> {
+    shared: Arc<Shared<B>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<B: Backend + Send + Sync + 'static> Reader<B> {
+    pub fn spawn<F>(shared: Arc<Shared<B>>, raw: bool, mut send: F) -> Reader<B>
+        where F: FnMut(EventResult) -> bool + Send + 'static
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let shared = shared.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if shared.suspending.load(Ordering::SeqCst) {
+                        thread::sleep(INTERVAL);
+                        continue;
+                    }
+                    match shared.peek_event(INTERVAL, raw) {
+                        Ok(Event::NoEvent) => (),
+                        ev => {
+                            let failed = ev.is_err();
+                            if !send(ev) || failed {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+        };
+        Reader { shared, stop, thread: Some(thread) }
+    }
+}
+
+impl<B: Backend> Reader<B> {
+    /// Stop the thread, waiting for it to finish.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.shared.backend.wake();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<B: Backend> Drop for Reader<B> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,138 @@
+use std::cmp;
+
+use super::{Cell, Color, Line, RustBox, Style, RB_NORMAL};
+use backend::Backend;
+use text;
+
+/// A rectangular part of the screen that is drawn to in coordinates of its own.
+///
+/// Created by `RustBox::region`, or by `Region::region` for a region inside another one.
+/// Everything drawn is translated by the region's origin and clipped to its bounds, so a part of
+/// the UI can't draw over its neighbours.
+///
+/// ```
+/// use rustbox::{Color, RustBox, RB_NORMAL};
+///
+/// let rb = RustBox::headless(20, 5);
+/// let sidebar = rb.region(15, 0, 5, 5);
+/// sidebar.print(1, 1, RB_NORMAL, Color::White, Color::Default, "clipped");
+/// assert_eq!(rb.get_cell(16, 1).unwrap().ch, 'c');
+/// assert_eq!(rb.get_cell(19, 1).unwrap().ch, 'p');
+///
+/// // Nested regions are clipped to their parent.
+/// let inner = sidebar.region(3, 1, 10, 10);
+/// assert_eq!((inner.width(), inner.height()), (2, 4));
+/// ```
+pub struct Region<'a, B: 'a + Backend> {
+    rb: &'a RustBox<B>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, B: Backend> Region<'a, B> {
+    pub(crate) fn new(rb: &'a RustBox<B>, x: usize, y: usize, width: usize, height: usize) -> Region<'a, B> {
+        Region { rb, x, y, width, height }
+    }
+
+    /// The column of the region's left edge on the screen.
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    /// The row of the region's top edge on the screen.
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// A region at (`x`, `y`) within this one, cut down to fit inside it.
+    pub fn region(&self, x: usize, y: usize, width: usize, height: usize) -> Region<'a, B> {
+        let x = cmp::min(x, self.width);
+        let y = cmp::min(y, self.height);
+        Region::new(self.rb,
+                    self.x + x,
+                    self.y + y,
+                    cmp::min(width, self.width - x),
+                    cmp::min(height, self.height - y))
+    }
+
+    /// Print `s` starting at (`x`, `y`) within the region, as `RustBox::print` does. Whatever
+    /// doesn't fit is left out.
+    pub fn print(&self, x: usize, y: usize, sty: Style, fg: Color, bg: Color, s: &str) {
+        if x < self.width && y < self.height {
+            let cell = Cell { ch: ' ', fg, bg, style: sty, underline: Color::Default };
+            self.print_styled(x, y, cell, s);
+        }
+    }
+
+    /// Print `s` starting at (`x`, `y`) within the region, as `RustBox::print_styled` does.
+    pub fn print_styled(&self, x: usize, y: usize, cell: Cell, s: &str) {
+        if x < self.width && y < self.height {
+            self.rb.print_until(self.x + x, self.y + y, self.x + self.width, cell, s);
+        }
+    }
+
+    /// Print the spans of `line` starting at (`x`, `y`) within the region, as
+    /// `RustBox::print_spans` does. Returns the column within the region after the last cell
+    /// drawn.
+    pub fn print_spans(&self, x: usize, y: usize, line: &Line) -> usize {
+        if x < self.width && y < self.height {
+            self.rb.print_spans_until(self.x + x, self.y + y, self.x + self.width, line) - self.x
+        } else {
+            x
+        }
+    }
+
+    /// Set the cell at (`x`, `y`) within the region, as `RustBox::print_char` does. A wide
+    /// character that would stick out of the region is replaced by a blank, as in `print`.
+    ///
+    /// ```
+    /// use rustbox::{Color, RustBox, RB_NORMAL};
+    ///
+    /// let rb = RustBox::headless(4, 1);
+    /// let left = rb.region(0, 0, 2, 1);
+    /// left.print_char(1, 0, RB_NORMAL, Color::Default, Color::Default, '日');
+    /// assert_eq!(rb.get_cell(1, 0).unwrap().ch, ' ');
+    /// ```
+    pub fn print_char(&self, x: usize, y: usize, sty: Style, fg: Color, bg: Color, ch: char) {
+        if x < self.width && y < self.height {
+            let ch = if x + text::char_width(ch) > self.width { ' ' } else { ch };
+            self.rb.print_char(self.x + x, self.y + y, sty, fg, bg, ch);
+        }
+    }
+
+    /// Set every cell of the region to `ch`. A wide character takes up two cells, and where
+    /// it doesn't fit in the last column, that column is left blank.
+    ///
+    /// ```
+    /// use rustbox::{Color, RustBox, RB_NORMAL};
+    ///
+    /// let rb = RustBox::headless(5, 1);
+    /// rb.region(0, 0, 5, 1).fill(RB_NORMAL, Color::Default, Color::Default, '日');
+    /// let row: String = (0..5).map(|x| rb.get_cell(x, 0).unwrap().ch).collect();
+    /// assert_eq!(row, "日 日  ");
+    /// ```
+    pub fn fill(&self, sty: Style, fg: Color, bg: Color, ch: char) {
+        self.rb.fill_rect(self.x, self.y, self.width, self.height, Cell {
+            ch,
+            fg,
+            bg,
+            style: sty,
+            underline: Color::Default,
+        });
+    }
+
+    /// Blank the region, as `RustBox::clear` does for the whole screen.
+    pub fn clear(&self) {
+        self.fill(RB_NORMAL, Color::Default, Color::Default, ' ');
+    }
+}
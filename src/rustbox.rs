@@ -1,37 +1,92 @@
 extern crate gag;
 extern crate num_traits;
+#[cfg(feature = "termbox")]
 extern crate termbox_sys as termbox;
+extern crate libc;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+#[cfg(feature = "futures")]
+extern crate futures_core;
 #[macro_use] extern crate bitflags;
 
+#[cfg(not(any(feature = "termbox", feature = "pure")))]
+compile_error!("rustbox needs at least one of the `termbox` and `pure` features");
+
 pub use self::style::{Style, RB_BOLD, RB_UNDERLINE, RB_REVERSE, RB_NORMAL};
+pub use self::style::{RB_ITALIC, RB_DIM, RB_STRIKETHROUGH, RB_BLINK, RB_INVISIBLE};
+pub use self::style::{RB_DOUBLE_UNDERLINE, RB_CURLY_UNDERLINE, RB_DOTTED_UNDERLINE, RB_DASHED_UNDERLINE};
 
+use std::cmp;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
 use std::io;
-use std::char;
 use std::default::Default;
 use std::ops::FnOnce;
-use std::sync::Mutex;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver};
 
 use num_traits::FromPrimitive;
-use termbox::RawEvent;
-use std::os::raw::c_int;
 use gag::Hold;
-use std::time::Duration;
+use reader::Reader;
+use std::time::{Duration, Instant};
 
+pub mod ansi;
+pub mod backend;
+pub mod cell;
 pub mod keyboard;
 pub mod mouse;
+mod job_control;
+mod palette;
+mod panic_hook;
+mod reader;
+pub mod region;
+pub mod span;
+pub mod text;
+#[cfg(feature = "futures")]
+mod stream;
 
 pub use self::running::running;
-pub use keyboard::Key;
-pub use mouse::Mouse;
-
-#[derive(Clone, Copy, Debug)]
+pub use backend::{Backend, DefaultBackend, HeadlessBackend};
+#[cfg(feature = "pure")]
+pub use backend::PureBackend;
+#[cfg(feature = "termbox")]
+pub use backend::TermboxBackend;
+pub use cell::{Cell, CellBuffer};
+pub use keyboard::{Key, Modifiers, MOD_ALT, MOD_CTRL, MOD_NONE, MOD_SHIFT};
+pub use mouse::{Button, Mouse};
+pub use region::Region;
+pub use span::{Line, Span};
+pub use text::str_width;
+#[cfg(feature = "futures")]
+pub use stream::EventStream;
+
+/// Something that happened in the terminal.
+///
+/// Events are `Clone` but not `Copy`, since `Paste` holds the pasted text.
+#[derive(Clone, Debug)]
 pub enum Event {
     KeyEventRaw(u8, u16, u32),
-    KeyEvent(Key),
+    KeyEvent(Key, Modifiers),
     ResizeEvent(i32, i32),
-    MouseEvent(Mouse, i32, i32),
+    /// A mouse event at (x, y). Which modifiers can be detected depends on the terminal and
+    /// the backend.
+    MouseEvent(Mouse, Modifiers, i32, i32),
+    /// Text pasted into the terminal, with `InitOptions::bracketed_paste` on. Line breaks are
+    /// always `\n`.
+    Paste(String),
+    /// A file descriptor registered with `RustBox::register_fd` is ready to be read, identified
+    /// by the token it was registered with.
+    Readable(usize),
+    /// A timer set with `RustBox::set_timer` or `RustBox::set_repeating_timer` went off,
+    /// identified by its token.
+    Timer(usize),
+    /// The terminal got the focus, with `InitOptions::focus_events` on.
+    FocusGained,
+    /// The terminal lost the focus, with `InitOptions::focus_events` on.
+    FocusLost,
     NoEvent
 }
 
@@ -48,7 +103,13 @@ pub enum InputMode {
     /// Same as `Esc` but enables mouse events
     EscMouse = 0x05,
     /// Same as `Alt` but enables mouse events
-    AltMouse = 0x06
+    AltMouse = 0x06,
+    /// Same as `EscMouse`, and also reports the mouse moving with no button held, see
+    /// `Mouse::Move`
+    EscMouseMotion = 0x0D,
+    /// Same as `AltMouse`, and also reports the mouse moving with no button held, see
+    /// `Mouse::Move`
+    AltMouseMotion = 0x0E,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -56,8 +117,13 @@ pub enum OutputMode {
     Current = 0,
     Normal = 1,
     EightBit = 2,  // 256 Colors
+    /// The 6x6x6 color cube only, indices 16 to 231. See `Color::cube`.
     WebSafe = 3,   // 216 Colors
+    /// The 24 step grayscale ramp only, indices 232 to 255. See `Color::gray`.
     Grayscale = 4,
+    /// 24-bit colors, for terminals that understand `38;2;r;g;b`. Backends that can't do this
+    /// fall back to 256 colors.
+    TrueColor = 5,
 }
 
 
@@ -71,10 +137,102 @@ pub enum Color {
     Magenta,
     Cyan,
     White,
+    /// An index into the 256 color palette: 0 to 15 are the system colors, 16 to 231 the
+    /// 6x6x6 color cube and 232 to 255 a grayscale ramp from dark to light.
     Byte(u16),
+    /// A 24-bit color, drawn as is in `OutputMode::TrueColor`.
+    Rgb(u8, u8, u8),
     Default,
 }
 impl Color {
+    /// The color at (`r`, `g`, `b`) in the 6x6x6 color cube, each level going from 0 to 5.
+    ///
+    /// These are the colors available in `OutputMode::WebSafe`.
+    ///
+    /// ```
+    /// use rustbox::Color;
+    ///
+    /// assert_eq!(Color::cube(0, 0, 0), Color::Byte(16));
+    /// assert_eq!(Color::cube(5, 0, 0), Color::Byte(196));
+    /// assert_eq!(Color::cube(5, 5, 5), Color::Byte(231));
+    /// ```
+    ///
+    /// Panics if a level is above 5.
+    pub fn cube(r: u8, g: u8, b: u8) -> Color {
+        assert!(r < 6 && g < 6 && b < 6, "Color cube levels go from 0 to 5");
+        Color::Byte(palette::CUBE_START + 36 * r as u16 + 6 * g as u16 + b as u16)
+    }
+
+    /// The shade `level` of the grayscale ramp, going from 0 (almost black) to 23 (almost
+    /// white).
+    ///
+    /// These are the colors available in `OutputMode::Grayscale`.
+    ///
+    /// ```
+    /// use rustbox::Color;
+    ///
+    /// assert_eq!(Color::gray(0), Color::Byte(232));
+    /// assert_eq!(Color::gray(23), Color::Byte(255));
+    /// ```
+    ///
+    /// Panics if `level` is above 23.
+    pub fn gray(level: u8) -> Color {
+        assert!(level < 24, "Grayscale levels go from 0 to 23");
+        Color::Byte(palette::GRAY_START + level as u16)
+    }
+
+    /// Convert to the nearest color that can be shown in `mode`.
+    ///
+    /// 24-bit colors are approximated by the 256 color palette, and anything that isn't one of
+    /// the eight named colors by the nearest of those in `Normal` mode. `Default` is always kept.
+    /// `print` and `print_char` do this for you. Palette indices above 255 are taken as 255.
+    ///
+    /// ```
+    /// use rustbox::{Color, OutputMode};
+    ///
+    /// assert_eq!(Color::Rgb(255, 0, 0).nearest(OutputMode::EightBit), Color::Byte(196));
+    /// assert_eq!(Color::Byte(300).nearest(OutputMode::EightBit), Color::Byte(255));
+    /// assert_eq!(Color::Byte(300).nearest(OutputMode::Normal), Color::White);
+    /// ```
+    pub fn nearest(self, mode: OutputMode) -> Color {
+        let rgb = match (self, mode) {
+            (Color::Default, _) | (_, OutputMode::TrueColor) => return self,
+            (Color::Rgb(r, g, b), _) => (r, g, b),
+            (Color::Byte(b), _) if b > 0xFF => return Color::Byte(0xFF).nearest(mode),
+            (Color::Byte(b), OutputMode::EightBit) => return Color::Byte(b),
+            (Color::Byte(b), OutputMode::WebSafe)
+                if (palette::CUBE_START..palette::GRAY_START).contains(&b) => return Color::Byte(b),
+            (Color::Byte(b), OutputMode::Grayscale)
+                if b >= palette::GRAY_START => return Color::Byte(b),
+            // The bright system colors look most like their normal counterparts.
+            (Color::Byte(b), OutputMode::Normal) | (Color::Byte(b), OutputMode::Current)
+                if b < 16 => return Color::from_256color(b % 8),
+            (Color::Byte(b), _) => palette::rgb(b as u8),
+            (named, OutputMode::EightBit) | (named, OutputMode::Normal) | (named, OutputMode::Current) => return named,
+            (named, _) => palette::rgb(named.as_256color() as u8),
+        };
+        let (r, g, b) = rgb;
+        match mode {
+            OutputMode::EightBit => Color::Byte(palette::nearest_256(r, g, b)),
+            OutputMode::WebSafe => Color::Byte(palette::nearest_cube(r, g, b)),
+            OutputMode::Grayscale => Color::Byte(palette::nearest_gray(r, g, b)),
+            _ => Color::from_256color(palette::nearest_8(r, g, b)),
+        }
+    }
+
+    /// Returns the palette index of this color.
+    ///
+    /// 24-bit colors are approximated by the 6x6x6 color cube. `Default` doesn't have an index,
+    /// and comes out as 0 like it does from `as_16color`, which is black in the palette; use
+    /// `nearest` first to tell it apart from the other colors.
+    ///
+    /// ```
+    /// use rustbox::Color;
+    ///
+    /// assert_eq!(Color::Red.as_256color(), 1);
+    /// assert_eq!(Color::Rgb(255, 0, 0).as_256color(), 196);
+    /// assert_eq!(Color::Default.as_256color(), 0);
+    /// ```
     pub fn as_256color(&self) -> u16 {
         match *self {
             Color::Black => 0x00,
@@ -86,10 +244,13 @@ impl Color {
             Color::Cyan => 0x06,
             Color::White => 0x07,
             Color::Byte(b) => b,
-            Color::Default => panic!("Attempted to cast default color to byte"),
+            Color::Rgb(r, g, b) => palette::nearest_cube(r, g, b),
+            Color::Default => 0x00,
         }
     }
 
+    /// Returns the termbox code of this color in 16 color mode. Colors outside of the eight
+    /// named ones are approximated by the nearest of those.
     pub fn as_16color(&self) -> u16 {
         match *self {
             Color::Default => 0x00,
@@ -101,7 +262,37 @@ impl Color {
             Color::Magenta => 0x06,
             Color::Cyan => 0x07,
             Color::White => 0x08,
-            Color::Byte(b) => panic!("Attempted to cast color byte {} to 16 color mode", b),
+            color => color.nearest(OutputMode::Normal).as_16color(),
+        }
+    }
+
+    /// Inverse of `as_256color`. The first eight indices map back to the named colors.
+    pub fn from_256color(b: u16) -> Color {
+        match b {
+            0x00 => Color::Black,
+            0x01 => Color::Red,
+            0x02 => Color::Green,
+            0x03 => Color::Yellow,
+            0x04 => Color::Blue,
+            0x05 => Color::Magenta,
+            0x06 => Color::Cyan,
+            0x07 => Color::White,
+            b => Color::Byte(b),
+        }
+    }
+
+    /// Inverse of `as_16color`. Values outside of the 16 color range map to `Default`.
+    pub fn from_16color(b: u16) -> Color {
+        match b {
+            0x01 => Color::Black,
+            0x02 => Color::Red,
+            0x03 => Color::Green,
+            0x04 => Color::Yellow,
+            0x05 => Color::Blue,
+            0x06 => Color::Magenta,
+            0x07 => Color::Cyan,
+            0x08 => Color::White,
+            _ => Color::Default,
         }
     }
 }
@@ -115,29 +306,41 @@ impl Default for Color {
 mod style {
     bitflags! {
         #[repr(C)]
-        flags Style: u16 {
+        flags Style: u32 {
             const TB_NORMAL_COLOR = 0x000F,
             const RB_BOLD = 0x0100,
             const RB_UNDERLINE = 0x0200,
             const RB_REVERSE = 0x0400,
+            // Termbox can't show these, and neither can terminals without the capability.
+            const RB_ITALIC = 0x0800,
+            const RB_DIM = 0x1000,
+            const RB_STRIKETHROUGH = 0x2000,
+            const RB_BLINK = 0x4000,
+            const RB_INVISIBLE = 0x8000,
+            // Underlines other than the plain one. Where they can't be shown, they are shown as
+            // `RB_UNDERLINE`, and if more than one is set the first of them wins.
+            const RB_DOUBLE_UNDERLINE = 0x1_0000,
+            const RB_CURLY_UNDERLINE = 0x2_0000,
+            const RB_DOTTED_UNDERLINE = 0x4_0000,
+            const RB_DASHED_UNDERLINE = 0x8_0000,
             const RB_NORMAL = 0x0000,
             const TB_ATTRIB = RB_BOLD.bits | RB_UNDERLINE.bits | RB_REVERSE.bits,
+            const RB_ANY_UNDERLINE = RB_UNDERLINE.bits | RB_DOUBLE_UNDERLINE.bits | RB_CURLY_UNDERLINE.bits
+                | RB_DOTTED_UNDERLINE.bits | RB_DASHED_UNDERLINE.bits,
         }
     }
 
     impl Style {
         pub fn from_color(color: super::Color) -> Style {
-            Style { bits: color.as_16color() & TB_NORMAL_COLOR.bits }
+            Style { bits: color.as_16color() as u32 & TB_NORMAL_COLOR.bits }
         }
 
         pub fn from_256color(color: super::Color) -> Style {
-            Style { bits: color.as_256color() }
+            Style { bits: color.as_256color() as u32 }
         }
     }
 }
 
-const NIL_RAW_EVENT: RawEvent = RawEvent { etype: 0, emod: 0, key: 0, ch: 0, w: 0, h: 0, x: 0, y: 0 };
-
 #[derive(Debug)]
 pub enum EventError {
    TermboxError,
@@ -176,42 +379,6 @@ impl FromPrimitive for EventError {
 
 pub type EventResult = Result<Event, EventError>;
 
-/// Unpack a RawEvent to an Event
-///
-/// if the `raw` parameter is true, then the Event variant will be the raw
-/// representation of the event.
-///     for instance KeyEventRaw instead of KeyEvent
-///
-/// This is useful if you want to interpret the raw event data yourself, rather
-/// than having rustbox translate it to its own representation.
-fn unpack_event(ev_type: c_int, ev: &RawEvent, raw: bool) -> EventResult {
-    match ev_type {
-        0 => Ok(Event::NoEvent),
-        1 => Ok(
-            if raw {
-                Event::KeyEventRaw(ev.emod, ev.key, ev.ch)
-            } else {
-                let k = match ev.key {
-                    0 => char::from_u32(ev.ch).map(|c| Key::Char(c)),
-                    a => Key::from_code(a),
-                };
-                if let Some(key) = k {
-                    Event::KeyEvent(key)
-                }
-                else {
-                    Event::KeyEvent(Key::Unknown(ev.key))
-                }
-            }),
-        2 => Ok(Event::ResizeEvent(ev.w, ev.h)),
-        3 => {
-            let mouse = Mouse::from_code(ev.key).unwrap_or(Mouse::Left);
-            Ok(Event::MouseEvent(mouse, ev.x, ev.y))
-        },
-        // `unwrap` is safe here because FromPrimitive for EventError only returns `Some`.
-        n => Err(FromPrimitive::from_isize(n as isize).unwrap()),
-    }
-}
-
 #[derive(Debug)]
 pub enum InitError {
     BufferStderrFailed(io::Error),
@@ -263,18 +430,25 @@ impl FromPrimitive for InitError {
    }
 }
 
-#[allow(missing_copy_implementations)]
-pub struct RustBox {
-    // We only bother to redirect stderr for the moment, since it's used for panic!
-    _stderr: Option<Hold>,
-    // RAII lock.
-    //
-    // Note that running *MUST* be the last field in the destructor, since destructors run in
-    // top-down order. Otherwise it will not properly protect the above fields.
-    _running: running::RunningGuard,
+struct Timer {
+    token: usize,
+    due: Instant,
+    // How often it repeats, if it does.
+    interval: Option<Duration>,
+}
 
-    // Store this so we know which colours to use
-    output_mode: OutputMode,
+// The parts of a `RustBox` that threads reading input on its behalf need as well.
+pub(crate) struct Shared<B> {
+    // Whatever we are drawing to. Termbox's backend holds the running lock.
+    pub backend: B,
+
+    // Events pushed by `push_event`, returned before anything is read from the backend.
+    queue: Mutex<VecDeque<Event>>,
+
+    // File descriptors registered with `register_fd`, with their tokens.
+    fds: Mutex<Vec<(RawFd, usize)>>,
+
+    timers: Mutex<Vec<Timer>>,
 
     // Used/obtained by methods that read from the terminal (or termbox's
     // internal state). Termbox is only thread safe to the extent that one
@@ -289,6 +463,209 @@ pub struct RustBox {
     // writes.
     // See https://github.com/nsf/termbox/commit/493dc1395c91174e97658ff15fa2380227faf28f
     output_lock: Mutex<()>,
+
+    // Set with `InitOptions::job_control`.
+    job_control: AtomicBool,
+
+    // Set while `RustBox::suspend` runs, so that threads reading events leave the input lock
+    // to it rather than taking it again as soon as they let go of it.
+    pub suspending: AtomicBool,
+}
+
+// Events are all the event loop can report, so a failure to take the terminal over again after
+// stopping comes out as this.
+fn resume_failed(_: InitError) -> EventError {
+    EventError::TermboxError
+}
+
+impl<B: Backend> Shared<B> {
+    pub fn poll_event(&self, raw: bool) -> EventResult {
+        let _lock = self.input_lock.lock();
+        loop {
+            if self.stop_pending() {
+                try!(self.stop_process().map_err(resume_failed));
+            }
+            if let Some(ev) = self.pop_event() {
+                return Ok(ev);
+            }
+            let now = Instant::now();
+            let timeout = match self.next_timer(now) {
+                Ok(token) => return Ok(Event::Timer(token)),
+                Err(due) => due.map(|due| due - now),
+            };
+            // Copied, so that registering doesn't have to wait for us.
+            let fds = self.fds().clone();
+            match try!(self.backend.wait_event(timeout, raw, &fds)) {
+                ref ev if self.is_stop_key(ev) => try!(self.stop_process().map_err(resume_failed)),
+                // We were woken up, most likely because an event was pushed or a file
+                // descriptor or timer was set, or a timer is due.
+                Event::NoEvent => continue,
+                ev => return Ok(ev),
+            }
+        }
+    }
+
+    pub fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult {
+        let _lock = self.input_lock.lock();
+        let timeout_at = Instant::now() + timeout;
+        loop {
+            if self.stop_pending() {
+                try!(self.stop_process().map_err(resume_failed));
+            }
+            if let Some(ev) = self.pop_event() {
+                return Ok(ev);
+            }
+            let now = Instant::now();
+            let deadline = match self.next_timer(now) {
+                Ok(token) => return Ok(Event::Timer(token)),
+                Err(Some(due)) if due < timeout_at => due,
+                Err(_) => timeout_at,
+            };
+            let left = if now < deadline { deadline - now } else { Duration::from_secs(0) };
+            let fds = self.fds().clone();
+            match try!(self.backend.wait_event(Some(left), raw, &fds)) {
+                ref ev if self.is_stop_key(ev) => try!(self.stop_process().map_err(resume_failed)),
+                // Keep waiting until the timeout, or return the timer we woke up for.
+                Event::NoEvent if Instant::now() < timeout_at || deadline < timeout_at => continue,
+                ev => return Ok(ev),
+            }
+        }
+    }
+
+    // Whether Ctrl-Z was pressed with job control enabled.
+    fn is_stop_key(&self, ev: &Event) -> bool {
+        match *ev {
+            Event::KeyEvent(Key::Ctrl('z'), _) | Event::KeyEventRaw(_, 0x1a, _) => {
+                self.job_control.load(Ordering::SeqCst)
+            },
+            _ => false,
+        }
+    }
+
+    // Whether a SIGTSTP came in with job control enabled. Interrupted waits come back as
+    // `NoEvent`, so this gets checked before waiting again.
+    fn stop_pending(&self) -> bool {
+        self.job_control.load(Ordering::SeqCst) && job_control::take_pending()
+    }
+
+    // Give the terminal back while `func` runs, and then show what was on the screen again. What
+    // was drawn but not presented yet is kept in the back buffer. The caller holds the input
+    // lock.
+    fn suspend<F>(&self, func: F) -> Result<(), InitError>
+        where F: FnOnce()
+    {
+        let _lock = self.output_lock.lock();
+        let (width, height) = (self.backend.width(), self.backend.height());
+        let shown: Vec<Option<(Cell, String)>> = (0..width * height)
+            .map(|i| self.backend.get_presented(i % width, i / width))
+            .collect();
+        let drawn: Vec<Option<(Cell, String)>> = (0..width * height)
+            .map(|i| self.backend.get_cluster(i % width, i / width))
+            .collect();
+
+        self.backend.suspend();
+        func();
+        try!(self.backend.resume());
+
+        // The terminal may have been resized meanwhile, `set_cluster` ignores what no longer
+        // fits.
+        let restore = |cells: Vec<Option<(Cell, String)>>| {
+            for (i, cell) in cells.into_iter().enumerate() {
+                if let Some((cell, rest)) = cell {
+                    self.backend.set_cluster(i % width, i / width, cell, &rest);
+                }
+            }
+        };
+        restore(shown);
+        self.backend.present();
+        restore(drawn);
+        Ok(())
+    }
+
+    // Stop until continued, and then queue a resize event. The caller holds the input lock.
+    fn stop_process(&self) -> Result<(), InitError> {
+        try!(self.suspend(job_control::stop));
+        let (width, height) = (self.backend.width(), self.backend.height());
+        self.push_event(Event::ResizeEvent(width as i32, height as i32));
+        Ok(())
+    }
+
+    fn push_event(&self, ev: Event) {
+        match self.queue.lock() {
+            Ok(mut queue) => queue.push_back(ev),
+            Err(poisoned) => poisoned.into_inner().push_back(ev),
+        }
+        self.backend.wake();
+    }
+
+    // The token of a timer that is due, which is rescheduled or removed, or otherwise when the
+    // next one will be.
+    fn next_timer(&self, now: Instant) -> Result<usize, Option<Instant>> {
+        let mut timers = self.timers();
+        let next = match timers.iter().enumerate().min_by_key(|&(_, timer)| timer.due) {
+            Some((i, timer)) if timer.due <= now => i,
+            Some((_, timer)) => return Err(Some(timer.due)),
+            None => return Err(None),
+        };
+        let token = timers[next].token;
+        match timers[next].interval {
+            Some(interval) => {
+                // Rather than firing over and over to catch up after falling behind, skip ahead.
+                let due = timers[next].due + interval;
+                timers[next].due = if due > now { due } else { now + interval };
+            }
+            None => {
+                timers.remove(next);
+            }
+        }
+        Ok(token)
+    }
+
+    fn timers(&self) -> MutexGuard<'_, Vec<Timer>> {
+        match self.timers.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn fds(&self) -> MutexGuard<'_, Vec<(RawFd, usize)>> {
+        match self.fds.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn pop_event(&self) -> Option<Event> {
+        match self.queue.lock() {
+            Ok(mut queue) => queue.pop_front(),
+            Err(poisoned) => poisoned.into_inner().pop_front(),
+        }
+    }
+}
+
+#[allow(missing_copy_implementations)]
+pub struct RustBox<B: Backend = DefaultBackend> {
+    // The thread started by `event_receiver`. It holds on to `shared`, so it has to be stopped
+    // first.
+    reader: Mutex<Option<Reader<B>>>,
+
+    // The backend, and what goes with reading input from it.
+    //
+    // Note that this *MUST* come before `_stderr`, since destructors run in top-down order.
+    // Otherwise buffered stderr would be written before the terminal is restored.
+    shared: Arc<Shared<B>>,
+
+    // We only bother to redirect stderr for the moment, since it's used for panic!
+    _stderr: Option<Hold>,
+
+    // Set with `InitOptions::panic_hook`, in which case it holds the stderr buffer instead.
+    _panic_hook: Option<panic_hook::Guard>,
+
+    // Set with `InitOptions::job_control`, keeps SIGTSTP caught.
+    _job_control: Option<job_control::Guard>,
+
+    // Store this so we know which colours to use
+    output_mode: OutputMode,
 }
 
 #[derive(Clone, Copy,Debug)]
@@ -311,6 +688,36 @@ pub struct InitOptions {
     /// your program, don't use RustBox's default pipe-based redirection; instead, redirect stderr
     /// to a log file or another process that is capable of handling it better.
     pub buffer_stderr: bool,
+
+    /// Use this option to receive pasted text as a single `Event::Paste` rather than one key
+    /// event per character, if the terminal supports bracketed paste.
+    ///
+    /// The termbox backend doesn't support this, and ignores it.
+    pub bracketed_paste: bool,
+
+    /// Use this option to receive `Event::FocusGained` and `Event::FocusLost` when the terminal
+    /// window gains or loses the focus, if the terminal supports focus reporting.
+    ///
+    /// The termbox backend doesn't support this, and ignores it.
+    pub focus_events: bool,
+
+    /// Use this option to restore the terminal if the program panics while RustBox is running.
+    ///
+    /// The panic hook this installs shuts the backend down, writes out stderr if it is being
+    /// buffered, and then prints the panic message, along with a backtrace if `RUST_BACKTRACE`
+    /// asks for one, where the default hook would print them to a terminal that is still in raw
+    /// mode, or into the stderr buffer. Panics while no RustBox with this option is `running`
+    /// are left to the hook that was there before, which includes any on a `HeadlessBackend`.
+    pub panic_hook: bool,
+
+    /// Use this option to have Ctrl-Z stop the program the way it does outside of raw mode.
+    ///
+    /// The terminal is given back while stopped, and once the program is continued the screen
+    /// is drawn again and a `ResizeEvent` is returned, since the terminal may have changed size
+    /// in the meantime. A SIGTSTP sent by other means is handled the same way, although the
+    /// termbox backend only notices it at the next event. Once every `RustBox` with this option
+    /// is dropped, SIGTSTP does what it did before. See also `RustBox::stop_process`.
+    pub job_control: bool,
 }
 
 impl Default for InitOptions {
@@ -319,28 +726,42 @@ impl Default for InitOptions {
             input_mode: InputMode::Current,
             output_mode: OutputMode::Current,
             buffer_stderr: false,
+            bracketed_paste: false,
+            focus_events: false,
+            panic_hook: false,
+            job_control: false,
         }
     }
 }
 
 mod running {
-    use std::sync::atomic::{self, AtomicBool};
+    use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 
     // The state of the RustBox is protected by the lock. Yay, global state!
     static RUSTBOX_RUNNING: AtomicBool = atomic::ATOMIC_BOOL_INIT;
 
+    // Backends other than termbox that have a terminal, of which there can be more than one.
+    static SHARED_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
     /// true iff RustBox is currently running. Beware of races here--don't rely on this for anything
     /// critical unless you happen to know that RustBox cannot change state when it is called (a good
     /// usecase would be checking to see if it's worth risking double printing backtraces to avoid
     /// having them swallowed up by RustBox).
+    ///
+    /// A RustBox counts as running from the time its backend takes the terminal over until it is
+    /// dropped, which a `HeadlessBackend` never does.
     pub fn running() -> bool {
-        RUSTBOX_RUNNING.load(atomic::Ordering::SeqCst)
+        RUSTBOX_RUNNING.load(atomic::Ordering::SeqCst) || SHARED_RUNNING.load(atomic::Ordering::SeqCst) > 0
     }
 
     // Internal RAII guard used to ensure we release the running lock whenever we acquire it.
+    // Shared ones only count towards `running`.
     #[allow(missing_copy_implementations)]
-    pub struct RunningGuard(());
+    pub struct RunningGuard {
+        shared: bool,
+    }
 
+    #[cfg_attr(not(feature = "termbox"), allow(dead_code))]
     pub fn run() -> Option<RunningGuard> {
         // Ensure that we are not already running and simultaneously set RUSTBOX_RUNNING using an
         // atomic swap. This ensures that contending threads don't trample each other.
@@ -349,15 +770,26 @@ mod running {
             None
         } else {
             // The RustBox was not already running, and now we have the lock.
-            Some(RunningGuard(()))
+            Some(RunningGuard { shared: false })
         }
     }
 
+    // Count as running alongside any other RustBox, for backends without global state.
+    #[cfg_attr(not(feature = "pure"), allow(dead_code))]
+    pub fn run_shared() -> RunningGuard {
+        SHARED_RUNNING.fetch_add(1, atomic::Ordering::SeqCst);
+        RunningGuard { shared: true }
+    }
+
     impl Drop for RunningGuard {
         fn drop(&mut self) {
-            // Indicate that we're free now. We could probably get away with lower atomicity here,
-            // but there's no reason to take that chance.
-            RUSTBOX_RUNNING.store(false, atomic::Ordering::SeqCst);
+            if self.shared {
+                SHARED_RUNNING.fetch_sub(1, atomic::Ordering::SeqCst);
+            } else {
+                // Indicate that we're free now. We could probably get away with lower atomicity
+                // here, but there's no reason to take that chance.
+                RUSTBOX_RUNNING.store(false, atomic::Ordering::SeqCst);
+            }
         }
     }
 }
@@ -381,30 +813,70 @@ impl RustBox {
     /// let rb = RustBox::init(InitOptions { input_mode: rustbox::InputMode::Esc, ..Default::default() });
     /// ```
     pub fn init(opts: InitOptions) -> Result<RustBox, InitError> {
-        let running = match running::run() {
-            Some(r) => r,
-            None => return Err(InitError::AlreadyOpen),
-        };
+        let backend = try!(DefaultBackend::init());
+        RustBox::with_backend(backend, opts)
+    }
+}
+
+#[cfg(feature = "termbox")]
+impl RustBox<TermboxBackend> {
+    /// Set a cell from termbox's own encoding of it, as `tb_change_cell` does.
+    ///
+    /// # Safety
+    ///
+    /// Nothing is checked: `ch` and the colors and attributes in `fg` and `bg` are sent to the
+    /// terminal as they are.
+    pub unsafe fn change_cell(&self, x: usize, y: usize, ch: u32, fg: u16, bg: u16) {
+        let _lock = self.shared.output_lock.lock();
+        self.backend().put_cell(x, y, termbox::RawCell { ch, fg, bg })
+    }
+}
+
+impl RustBox<HeadlessBackend> {
+    /// Create a RustBox that draws into an in-memory grid of the given size instead of the
+    /// terminal. See `HeadlessBackend` for details.
+    ///
+    /// ```
+    /// use rustbox::{Color, Event, Key, RustBox};
+    ///
+    /// let rb = RustBox::headless(20, 5);
+    /// rb.print(1, 1, rustbox::RB_BOLD, Color::White, Color::Black, "Hello");
+    /// rb.present();
+    /// assert_eq!(rb.get_cell(1, 1).unwrap().ch, 'H');
+    ///
+    /// rb.push_event(Event::KeyEvent(Key::Char('q'), rustbox::MOD_ALT));
+    /// match rb.poll_event(false) {
+    ///     Ok(Event::KeyEvent(Key::Char('q'), rustbox::MOD_ALT)) => (),
+    ///     _ => panic!("expected the pushed event"),
+    /// }
+    /// ```
+    pub fn headless(width: usize, height: usize) -> RustBox<HeadlessBackend> {
+        RustBox::from_parts(HeadlessBackend::new(width, height), None)
+    }
+}
 
-        let stderr = if opts.buffer_stderr {
-            Some(try!(Hold::stderr().map_err(|e| InitError::BufferStderrFailed(e))))
+impl<B: Backend> RustBox<B> {
+    /// Initialize rustbox on top of an already initialized backend.
+    ///
+    /// `RustBox::init` is a shorthand for doing this with a `TermboxBackend`.
+    pub fn with_backend(backend: B, opts: InitOptions) -> Result<RustBox<B>, InitError> {
+        let mut stderr = if opts.buffer_stderr {
+            Some(try!(Hold::stderr().map_err(InitError::BufferStderrFailed)))
+        } else {
+            None
+        };
+        let hook = if opts.panic_hook {
+            Some(panic_hook::register(backend.restorer(), stderr.take()))
         } else {
             None
         };
 
-        // Create the RustBox.
-        let mut rb = unsafe { match termbox::tb_init() {
-            0 => RustBox {
-                _stderr: stderr,
-                _running: running,
-                output_mode: OutputMode::Current,
-                input_lock: Mutex::new(()),
-                output_lock: Mutex::new(()),
-            },
-            res => {
-                return Err(FromPrimitive::from_isize(res as isize).unwrap())
-            }
-        }};
+        let mut rb = RustBox::from_parts(backend, stderr);
+        rb._panic_hook = hook;
+        if opts.job_control {
+            rb._job_control = Some(job_control::install());
+            rb.shared.job_control.store(true, Ordering::SeqCst);
+        }
         match opts.input_mode {
             InputMode::Current => (),
             _ => rb.set_input_mode(opts.input_mode),
@@ -413,161 +885,513 @@ impl RustBox {
             OutputMode::Current => (),
             _ => rb.set_output_mode(opts.output_mode),
         }
+        if opts.bracketed_paste {
+            rb.backend().set_bracketed_paste(true);
+        }
+        if opts.focus_events {
+            rb.backend().set_focus_events(true);
+        }
 
         Ok(rb)
     }
 
+    fn from_parts(backend: B, stderr: Option<Hold>) -> RustBox<B> {
+        RustBox {
+            reader: Mutex::new(None),
+            shared: Arc::new(Shared {
+                backend,
+                queue: Mutex::new(VecDeque::new()),
+                fds: Mutex::new(Vec::new()),
+                timers: Mutex::new(Vec::new()),
+                input_lock: Mutex::new(()),
+                output_lock: Mutex::new(()),
+                job_control: AtomicBool::new(false),
+                suspending: AtomicBool::new(false),
+            }),
+            _stderr: stderr,
+            _panic_hook: None,
+            _job_control: None,
+            output_mode: OutputMode::Current,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.shared.backend
+    }
+
     pub fn width(&self) -> usize {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        unsafe { termbox::tb_width() as usize }
+        self.backend().width()
     }
 
     pub fn height(&self) -> usize {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        unsafe { termbox::tb_height() as usize }
+        self.backend().height()
     }
 
     pub fn clear(&self) {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        unsafe { termbox::tb_clear() }
+        self.backend().clear()
     }
 
     pub fn present(&self) {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        unsafe { termbox::tb_present() }
+        self.backend().present()
     }
 
     pub fn set_cursor(&self, x: isize, y: isize) {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        unsafe { termbox::tb_set_cursor(x as c_int, y as c_int) }
+        self.backend().set_cursor(x, y)
     }
 
-    pub unsafe fn change_cell(&self, x: usize, y: usize, ch: u32, fg: u16, bg: u16) {
-        termbox::tb_change_cell(x as c_int, y as c_int, ch, fg, bg)
+    /// Returns the cell at (`x`, `y`) in the back buffer, or `None` if it is off screen.
+    ///
+    /// This reflects what has been drawn since the last `clear`, not necessarily what has been
+    /// presented to the terminal.
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<Cell> {
+        let _lock = self.shared.output_lock.lock();
+
+        self.backend().get_cell(x, y)
+    }
+
+    /// Returns the cell at (`x`, `y`) in the back buffer as `get_cell` does, along with the rest
+    /// of the grapheme cluster it holds after `ch`, such as combining marks or the rest of an
+    /// emoji sequence. The rest is empty for most cells, and always with the termbox backend.
+    ///
+    /// ```
+    /// use rustbox::{Color, RustBox, RB_NORMAL};
+    ///
+    /// let rb = RustBox::headless(5, 1);
+    /// rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "e\u{301}!");
+    /// let (cell, rest) = rb.get_cluster(0, 0).unwrap();
+    /// assert_eq!((cell.ch, rest.as_str()), ('e', "\u{301}"));
+    /// assert_eq!(rb.get_cell(1, 0).unwrap().ch, '!');
+    /// ```
+    pub fn get_cluster(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        let _lock = self.shared.output_lock.lock();
+
+        self.backend().get_cluster(x, y)
+    }
+
+    /// Borrow the whole back buffer for reading.
+    ///
+    /// The returned view holds the output lock, so drawing from another thread blocks until it
+    /// is dropped.
+    pub fn cell_buffer(&self) -> CellBuffer<'_, B> {
+        let lock = match self.shared.output_lock.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        CellBuffer::new(lock, self.backend())
+    }
+
+    /// A region of the screen that can be drawn to in coordinates relative to (`x`, `y`), and
+    /// that clips what is drawn to `width` by `height` cells. See `Region`.
+    pub fn region(&self, x: usize, y: usize, width: usize, height: usize) -> Region<'_, B> {
+        Region::new(self, x, y, width, height)
     }
 
+    /// Print `s` starting at (`x`, `y`). Whatever goes past the right edge of the screen is left
+    /// out.
+    ///
+    /// Each grapheme cluster, what reads as one character, goes into a cell of its own and takes
+    /// up as many columns as the terminal gives it, see `str_width`. Wide ones cover the cell to
+    /// their right, and one that would only half fit at the end of the line is replaced by a
+    /// blank. Zero-width characters with nothing to attach to at the start of `s` are left out.
+    /// The termbox backend only keeps the first `char` of each cluster, and draws it alone.
+    ///
+    /// Colors that can't be shown in the current output mode are replaced by the nearest ones
+    /// that can, see `Color::nearest`.
+    ///
+    /// ```
+    /// use rustbox::{Color, RustBox, RB_NORMAL};
+    ///
+    /// let rb = RustBox::headless(5, 1);
+    /// rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "日本語");
+    /// assert_eq!(rb.get_cell(2, 0).unwrap().ch, '本');
+    /// // 語 would only half fit.
+    /// assert_eq!(rb.get_cell(4, 0).unwrap().ch, ' ');
+    /// ```
     pub fn print(&self, x: usize, y: usize, sty: Style, fg: Color, bg: Color, s: &str) {
-        let _lock = self.output_lock.lock();
+        let cell = Cell { ch: ' ', fg, bg, style: sty, underline: Color::Default };
+        self.print_until(x, y, usize::MAX, cell, s);
+    }
 
-        let fg_int;
-        let bg_int;
+    /// Print `s` as `print` does, in the colors and style of `cell`, which also carries the
+    /// underline color. `cell.ch` isn't used.
+    ///
+    /// ```
+    /// use rustbox::{Cell, Color, RustBox, RB_CURLY_UNDERLINE};
+    ///
+    /// let rb = RustBox::headless(20, 1);
+    /// let error = Cell {
+    ///     ch: ' ',
+    ///     fg: Color::Default,
+    ///     bg: Color::Default,
+    ///     style: RB_CURLY_UNDERLINE,
+    ///     underline: Color::Red,
+    /// };
+    /// rb.print_styled(0, 0, error, "mispelled");
+    /// assert_eq!(rb.get_cell(0, 0).unwrap().underline, Color::Red);
+    /// ```
+    pub fn print_styled(&self, x: usize, y: usize, cell: Cell, s: &str) {
+        self.print_until(x, y, usize::MAX, cell, s);
+    }
 
-        match self.output_mode {
-            // 256 color mode
-            OutputMode::EightBit => {
-                fg_int = Style::from_256color(fg) | (sty & style::TB_ATTRIB);
-                bg_int = Style::from_256color(bg);
-            },
+    /// Print the spans of `line` one after another, starting at (`x`, `y`), as `print` would.
+    /// Returns the column after the last cell drawn.
+    ///
+    /// ```
+    /// use rustbox::{Color, Line, RustBox, Span, RB_BOLD, RB_NORMAL};
+    ///
+    /// let rb = RustBox::headless(20, 1);
+    /// let line = Line(vec![
+    ///     Span::new("error", RB_BOLD, Color::Red, Color::Default),
+    ///     Span::new(": 変数", RB_NORMAL, Color::Default, Color::Default),
+    /// ]);
+    /// assert_eq!(rb.print_spans(1, 0, &line), 12);
+    /// assert_eq!(rb.get_cell(6, 0).unwrap().ch, ':');
+    /// ```
+    pub fn print_spans(&self, x: usize, y: usize, line: &Line) -> usize {
+        self.print_spans_until(x, y, usize::MAX, line)
+    }
+
+    /// Print `s`, a line of text with ANSI escape sequences, starting at (`x`, `y`). The SGR
+    /// sequences in it style the text, see the `ansi` module. Tabs stop every 8 columns from `x`.
+    /// Returns the column after the last cell drawn.
+    ///
+    /// ```
+    /// use rustbox::{Color, RustBox};
+    ///
+    /// let rb = RustBox::headless(20, 1);
+    /// assert_eq!(rb.print_ansi(0, 0, "\x1b[1;32mok\x1b[0m done"), 7);
+    /// assert_eq!(rb.get_cell(1, 0).unwrap().fg, Color::Green);
+    /// assert_eq!(rb.get_cell(3, 0).unwrap().ch, 'd');
+    ///
+    /// assert_eq!(rb.print_ansi(0, 0, "a\tb"), 9);
+    /// assert_eq!(rb.get_cell(1, 0).unwrap().ch, ' ');
+    /// assert_eq!(rb.get_cell(8, 0).unwrap().ch, 'b');
+    /// ```
+    pub fn print_ansi(&self, x: usize, y: usize, s: &str) -> usize {
+        self.print_spans(x, y, &ansi::parse_line(s))
+    }
+
+    // Print the spans of `line`, leaving out whatever would go at or past column `end`.
+    pub(crate) fn print_spans_until(&self, x: usize, y: usize, end: usize, line: &Line) -> usize {
+        let _lock = self.shared.output_lock.lock();
 
-            // 16 color mode
-            _ => {
-                fg_int = Style::from_color(fg) | (sty & style::TB_ATTRIB);
-                bg_int = Style::from_color(bg);
+        line.0.iter().fold(x, |x, span| {
+            let cell = Cell { ch: ' ', fg: span.fg, bg: span.bg, style: span.style, underline: span.underline };
+            self.draw_text(x, y, end, cell, &span.text)
+        })
+    }
+
+    // Print `s` in the colors and style of `cell`, leaving out whatever would go at or past
+    // column `end`.
+    pub(crate) fn print_until(&self, x: usize, y: usize, end: usize, cell: Cell, s: &str) {
+        let _lock = self.shared.output_lock.lock();
+
+        self.draw_text(x, y, end, cell, s);
+    }
+
+    // `print_until` for callers that hold the output lock. Returns the column after the last
+    // cell drawn.
+    fn draw_text(&self, x: usize, y: usize, end: usize, cell: Cell, s: &str) -> usize {
+        let end = cmp::min(end, self.backend().width());
+        let blank = Cell {
+            ch: ' ',
+            fg: cell.fg.nearest(self.output_mode),
+            bg: cell.bg.nearest(self.output_mode),
+            style: cell.style,
+            underline: cell.underline.nearest(self.output_mode),
+        };
+        let mut x = x;
+        for g in text::graphemes(s) {
+            let width = text::grapheme_width(g);
+            let mut chars = g.chars();
+            let ch = match chars.next() {
+                Some(ch) if width > 0 => ch,
+                _ => continue,
+            };
+            if x + width > end {
+                for x in x..end {
+                    self.backend().set_cell(x, y, blank);
+                }
+                return cmp::max(x, end);
+            }
+            self.backend().set_cluster(x, y, Cell { ch, ..blank }, chars.as_str());
+            for x in x + 1..x + width {
+                self.backend().set_cell(x, y, blank);
             }
+            x += width;
         }
+        x
+    }
+
+    // Fill the rectangle at (`x`, `y`) with `cell`, as `draw_text` would a row of it. A wide
+    // character takes up two cells, and the last column is left blank if it doesn't fit.
+    pub(crate) fn fill_rect(&self, x: usize, y: usize, width: usize, height: usize, cell: Cell) {
+        let _lock = self.shared.output_lock.lock();
 
-        for (i, ch) in s.chars().enumerate() {
-            unsafe {
-                self.change_cell(x+i, y, ch as u32, fg_int.bits(), bg_int.bits());
+        let cell = Cell {
+            fg: cell.fg.nearest(self.output_mode),
+            bg: cell.bg.nearest(self.output_mode),
+            underline: cell.underline.nearest(self.output_mode),
+            ..cell
+        };
+        let blank = Cell { ch: ' ', ..cell };
+        let step = cmp::max(text::char_width(cell.ch), 1);
+        let end = x + width;
+        for y in y..y + height {
+            let mut x = x;
+            while x < end {
+                if x + step > end {
+                    self.backend().set_cell(x, y, blank);
+                    x += 1;
+                    continue;
+                }
+                self.backend().set_cell(x, y, cell);
+                for x in x + 1..x + step {
+                    self.backend().set_cell(x, y, blank);
+                }
+                x += step;
             }
         }
     }
 
     pub fn print_char(&self, x: usize, y: usize, sty: Style, fg: Color, bg: Color, ch: char) {
-        let _lock = self.output_lock.lock();
+        let _lock = self.shared.output_lock.lock();
 
-        let fg_int;
-        let bg_int;
+        let fg = fg.nearest(self.output_mode);
+        let bg = bg.nearest(self.output_mode);
+        self.backend().set_cell(x, y, Cell { ch, fg, bg, style: sty, underline: Color::Default });
+    }
 
-        match self.output_mode {
-            // 256 color mode
-            OutputMode::EightBit => {
-                fg_int = Style::from_256color(fg) | (sty & style::TB_ATTRIB);
-                bg_int = Style::from_256color(bg);
-            },
+    pub fn poll_event(&self, raw: bool) -> EventResult {
+        self.shared.poll_event(raw)
+    }
 
-            // 16 color mode
-            _ => {
-                fg_int = Style::from_color(fg) | (sty & style::TB_ATTRIB);
-                bg_int = Style::from_color(bg);
-            }
+    pub fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult {
+        self.shared.peek_event(timeout, raw)
+    }
+
+    /// Read events on a thread of their own, and return them as a `futures::Stream`.
+    ///
+    /// The stream doesn't depend on any particular executor, so it works with tokio's `select!`
+    /// alongside sockets and timers as well as with any other runtime. Events are read as by
+    /// `poll_event`, including the ones queued by `push_event`, until the stream is dropped; it
+    /// ends after returning an error. Only available with the `futures` feature.
+    ///
+    /// Events are read ahead for as long as the stream exists, and held without limit until it
+    /// is polled. Reading pauses while `suspend` runs.
+    #[cfg(feature = "futures")]
+    pub fn events(&self, raw: bool) -> EventStream<'_, B>
+        where B: Send + Sync + 'static
+    {
+        EventStream::new(self, raw)
+    }
+
+    /// Read events on a thread of their own, and return a channel they are sent to.
+    ///
+    /// Events are read as by `poll_event`, including the ones queued by `push_event`. The thread
+    /// stops, and the channel disconnects, after an error, when `suspend` is called or the
+    /// `RustBox` is dropped, or when this is called again. Dropping the receiver stops it too,
+    /// although only once the next event comes in.
+    ///
+    /// ```
+    /// use rustbox::{Event, Key, RustBox};
+    ///
+    /// let rb = RustBox::headless(20, 5);
+    /// let events = rb.event_receiver(false);
+    /// rb.push_event(Event::KeyEvent(Key::Enter, rustbox::MOD_NONE));
+    /// match events.recv() {
+    ///     Ok(Ok(Event::KeyEvent(Key::Enter, _))) => (),
+    ///     _ => panic!("expected the pushed event"),
+    /// }
+    ///
+    /// rb.suspend(|| ()).unwrap();
+    /// assert!(events.recv().is_err());
+    /// ```
+    pub fn event_receiver(&self, raw: bool) -> Receiver<EventResult>
+        where B: Send + Sync + 'static
+    {
+        let mut reader = self.reader();
+        if let Some(mut old) = reader.take() {
+            old.stop();
         }
-        unsafe {
-            self.change_cell(x, y, ch as u32, fg_int.bits(), bg_int.bits());
+        let (tx, rx) = mpsc::channel();
+        *reader = Some(Reader::spawn(self.shared.clone(), raw, move |ev| tx.send(ev).is_ok()));
+        rx
+    }
+
+    /// Have `poll_event` and `peek_event` also return `Event::Readable(token)` when `fd` is
+    /// ready to be read, or has hung up.
+    ///
+    /// Like poll(2), the event is returned every time events are read for as long as there is
+    /// something to read, so read from `fd` or deregister it before looking for the next event.
+    /// Registering a file descriptor again replaces its token.
+    ///
+    /// The termbox and pure Rust backends wait for registered file descriptors together with
+    /// the terminal. Other backends may only check them every 10ms, see
+    /// `Backend::wait_event`.
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use std::os::unix::net::UnixStream;
+    /// use std::time::Duration;
+    /// use rustbox::{Event, RustBox};
+    ///
+    /// let rb = RustBox::headless(20, 5);
+    /// let (mut tx, rx) = UnixStream::pair().unwrap();
+    /// rb.register(&rx, 7);
+    /// tx.write_all(b"ping").unwrap();
+    /// match rb.peek_event(Duration::from_secs(1), false) {
+    ///     Ok(Event::Readable(7)) => (),
+    ///     _ => panic!("expected the socket to be readable"),
+    /// }
+    /// ```
+    pub fn register_fd(&self, fd: RawFd, token: usize) {
+        {
+            let mut fds = self.shared.fds();
+            fds.retain(|&(registered, _)| registered != fd);
+            fds.push((fd, token));
         }
+        // Have a waiting call pick it up.
+        self.backend().wake();
     }
 
-    pub fn poll_event(&self, raw: bool) -> EventResult {
-        let _lock = self.input_lock.lock();
-        let mut ev = NIL_RAW_EVENT;
-        let rc = unsafe {
-            termbox::tb_poll_event(&mut ev)
-        };
-        unpack_event(rc, &ev, raw)
+    /// Like `register_fd`, for anything that has a file descriptor.
+    pub fn register<S: AsRawFd>(&self, source: &S, token: usize) {
+        self.register_fd(source.as_raw_fd(), token);
     }
 
-    pub fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult {
-        let _lock = self.input_lock.lock();
-        let mut ev = NIL_RAW_EVENT;
-        let rc = unsafe {
-            termbox::tb_peek_event(&mut ev, (timeout.as_secs() * 1000 + timeout.subsec_nanos() as u64 / 1000000) as c_int)
-        };
-        unpack_event(rc, &ev, raw)
+    /// Stop watching the file descriptors registered with `token`.
+    pub fn deregister(&self, token: usize) {
+        self.shared.fds().retain(|&(_, registered)| registered != token);
+        self.backend().wake();
     }
 
-    pub fn set_input_mode(&self, mode: InputMode) {
-        let _lock = self.output_lock.lock();
+    /// Have `poll_event` or `peek_event` return `Event::Timer(token)` once `delay` has passed.
+    ///
+    /// While waiting for input, the next timer is waited for as well, so nothing has to poll in
+    /// between. Setting a timer again with the same token replaces it.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rustbox::{Event, RustBox};
+    ///
+    /// let rb = RustBox::headless(20, 5);
+    /// rb.set_timer(1, Duration::from_millis(10));
+    /// match rb.poll_event(false) {
+    ///     Ok(Event::Timer(1)) => (),
+    ///     _ => panic!("expected the timer"),
+    /// }
+    /// ```
+    pub fn set_timer(&self, token: usize, delay: Duration) {
+        self.add_timer(Timer { token, due: Instant::now() + delay, interval: None });
+    }
+
+    /// Like `set_timer`, but the timer keeps going off every `interval` until it is cancelled.
+    ///
+    /// If events aren't read for longer than `interval`, it goes off once rather than once for
+    /// every interval missed.
+    pub fn set_repeating_timer(&self, token: usize, interval: Duration) {
+        self.add_timer(Timer { token, due: Instant::now() + interval, interval: Some(interval) });
+    }
+
+    /// Cancel the timer set with `token`, if it hasn't gone off yet or repeats.
+    pub fn cancel_timer(&self, token: usize) {
+        self.shared.timers().retain(|timer| timer.token != token);
+    }
 
-        unsafe {
-            termbox::tb_select_input_mode(mode as c_int);
+    fn add_timer(&self, timer: Timer) {
+        {
+            let mut timers = self.shared.timers();
+            timers.retain(|old| old.token != timer.token);
+            timers.push(timer);
         }
+        // Have a waiting call pick it up.
+        self.backend().wake();
     }
 
-    pub fn set_output_mode(&mut self, mode: OutputMode) {
-        let _lock = self.output_lock.lock();
+    fn reader(&self) -> MutexGuard<'_, Option<Reader<B>>> {
+        match self.reader.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
 
-        self.output_mode = mode;
+    /// Give the terminal back and stop the program, as Ctrl-Z does with
+    /// `InitOptions::job_control`.
+    ///
+    /// This returns once the program is continued, with the screen drawn again and a
+    /// `ResizeEvent` queued, or with an error if the terminal couldn't be taken over again.
+    pub fn stop_process(&self) -> Result<(), InitError> {
+        let _lock = self.shared.input_lock.lock();
+        self.shared.stop_process()
+    }
 
-        unsafe {
-            termbox::tb_select_output_mode(mode as c_int);
+    /// Queue an event to be returned by the next call to `poll_event` or `peek_event`.
+    ///
+    /// Queued events are returned ahead of input from the backend, in the order they were
+    /// pushed, and are never converted to or from their raw representation. A call that is
+    /// already waiting for input is woken up if the backend supports it, as all of the ones
+    /// in this crate do; otherwise the event is seen the next time input is looked for.
+    pub fn push_event(&self, ev: Event) {
+        self.shared.push_event(ev);
+    }
+
+    pub fn set_input_mode(&self, mode: InputMode) {
+        let _lock = self.shared.output_lock.lock();
+
+        self.backend().set_input_mode(mode);
+    }
+
+    pub fn set_output_mode(&mut self, mode: OutputMode) {
+        let _lock = self.shared.output_lock.lock();
+
+        if let OutputMode::Current = mode {
+            return;
         }
+        self.output_mode = mode;
+        self.backend().set_output_mode(mode);
     }
 
     /// Convenience method to lock all (both input/output) access to
     /// Rustbox, shutdown termbox itself, and then defer to the caller (via F,
     /// while access is still locked). Once F completes, termbox is started and
     /// the locks are released.
-    pub fn suspend<F>(&self, func: F)
+    ///
+    /// The input and output modes are set up again, and what was on the screen before
+    /// suspending is put back, while what was drawn but not presented yet stays in the back
+    /// buffer. Termbox's screen can't be read back, so with `TermboxBackend` the back buffer is
+    /// what gets put back. An error means the terminal couldn't be taken over again, in which
+    /// case it is left the way `func` left it.
+    ///
+    /// This also stops the thread started by `event_receiver`, while the thread of an `events`
+    /// stream waits for this to return.
+    pub fn suspend<F>(&self, func: F) -> Result<(), InitError>
         where F: FnOnce() -> ()
     {
-        // Lock I/O until we've resumed.
-        let _input_lock = self.input_lock.lock();
-        let _output_lock = self.output_lock.lock();
-
-        unsafe {
-            termbox::tb_shutdown();
+        // Before locking, since the thread may be waiting for the input lock.
+        if let Some(mut reader) = self.reader().take() {
+            reader.stop();
         }
 
-        func();
-
-        unsafe {
-            termbox::tb_init();
-        }
-    }
-}
-
-impl Drop for RustBox {
-    fn drop(&mut self) {
-        // Since only one instance of the RustBox is ever accessible, we should not
-        // need to do this atomically.
-        // Note: we should definitely have RUSTBOX_RUNNING = true here.
-        unsafe {
-            termbox::tb_shutdown();
-        }
+        self.shared.suspending.store(true, Ordering::SeqCst);
+        self.shared.backend.wake();
+        let result = {
+            // Lock I/O until we've resumed.
+            let _input_lock = self.shared.input_lock.lock();
+            self.shared.suspend(func)
+        };
+        self.shared.suspending.store(false, Ordering::SeqCst);
+        result
     }
 }
@@ -0,0 +1,53 @@
+use super::{Color, Style, RB_NORMAL};
+use text::str_width;
+
+/// A piece of text drawn in one style, see `RustBox::print_spans`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Span {
+    pub text: String,
+    pub fg: Color,
+    pub bg: Color,
+    pub style: Style,
+    /// The color of underlines, or `Color::Default` to underline in `fg`.
+    pub underline: Color,
+}
+
+impl Span {
+    /// A span with underlines, if any, in `fg`.
+    pub fn new<S: Into<String>>(text: S, sty: Style, fg: Color, bg: Color) -> Span {
+        Span { text: text.into(), fg, bg, style: sty, underline: Color::Default }
+    }
+
+    /// A span in the terminal's default colors, without attributes.
+    pub fn raw<S: Into<String>>(text: S) -> Span {
+        Span::new(text, RB_NORMAL, Color::Default, Color::Default)
+    }
+
+    /// The number of columns the span takes up, see `str_width`.
+    pub fn width(&self) -> usize {
+        str_width(&self.text)
+    }
+}
+
+/// Spans that are drawn one after another on a line.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Line(pub Vec<Span>);
+
+impl Line {
+    /// The number of columns the line takes up.
+    pub fn width(&self) -> usize {
+        self.0.iter().map(Span::width).sum()
+    }
+}
+
+impl From<Vec<Span>> for Line {
+    fn from(spans: Vec<Span>) -> Line {
+        Line(spans)
+    }
+}
+
+impl From<Span> for Line {
+    fn from(span: Span) -> Line {
+        Line(vec![span])
+    }
+}
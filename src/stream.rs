@@ -0,0 +1,122 @@
+//! Events as a `futures::Stream`.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use reader::Reader;
+use {Backend, EventResult, RustBox};
+
+// Events read by the thread, waiting for the stream to be polled.
+struct Inbox {
+    events: VecDeque<EventResult>,
+    waker: Option<Waker>,
+    // Set once the thread has stopped reading.
+    closed: bool,
+}
+
+// Marks the inbox closed once the thread is done with it, however it ended.
+struct Closer(Arc<Mutex<Inbox>>);
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        let mut inbox = lock(&self.0);
+        inbox.closed = true;
+        if let Some(waker) = inbox.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A stream of the events of a `RustBox`, see `RustBox::events`.
+///
+/// Events that were read but not polled yet are queued without a limit.
+pub struct EventStream<'a, B: Backend + 'a> {
+    inbox: Arc<Mutex<Inbox>>,
+    // Stops the thread when the stream is dropped.
+    _reader: Reader<B>,
+    _rustbox: PhantomData<&'a RustBox<B>>,
+}
+
+fn lock(inbox: &Mutex<Inbox>) -> MutexGuard<'_, Inbox> {
+    match inbox.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl<'a, B: Backend + Send + Sync + 'static> EventStream<'a, B> {
+    pub(crate) fn new(rb: &'a RustBox<B>, raw: bool) -> EventStream<'a, B> {
+        let inbox = Arc::new(Mutex::new(Inbox { events: VecDeque::new(), waker: None, closed: false }));
+        let reader = {
+            let closer = Closer(inbox.clone());
+            Reader::spawn(rb.shared.clone(), raw, move |ev| {
+                let mut inbox = lock(&closer.0);
+                inbox.events.push_back(ev);
+                if let Some(waker) = inbox.waker.take() {
+                    waker.wake();
+                }
+                true
+            })
+        };
+        EventStream { inbox, _reader: reader, _rustbox: PhantomData }
+    }
+}
+
+impl<'a, B: Backend> Stream for EventStream<'a, B> {
+    type Item = EventResult;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<EventResult>> {
+        let mut inbox = lock(&self.inbox);
+        if let Some(ev) = inbox.events.pop_front() {
+            return Poll::Ready(Some(ev));
+        }
+        if inbox.closed {
+            return Poll::Ready(None);
+        }
+        inbox.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use futures_core::Stream;
+
+    use {Event, Key, RustBox, MOD_NONE};
+
+    // Poll `stream` until it has something, the way an executor would.
+    fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(item) = Pin::new(&mut *stream).poll_next(&mut cx) {
+                return item;
+            }
+        }
+    }
+
+    #[test]
+    fn events_across_suspend() {
+        let rb = RustBox::headless(10, 2);
+        let mut events = rb.events(false);
+        rb.push_event(Event::KeyEvent(Key::Enter, MOD_NONE));
+        match next(&mut events) {
+            Some(Ok(Event::KeyEvent(Key::Enter, _))) => (),
+            ev => panic!("expected the pushed event, got {:?}", ev),
+        }
+
+        rb.suspend(|| ()).unwrap();
+        rb.push_event(Event::KeyEvent(Key::Tab, MOD_NONE));
+        match next(&mut events) {
+            Some(Ok(Event::KeyEvent(Key::Tab, _))) => (),
+            ev => panic!("expected the pushed event, got {:?}", ev),
+        }
+    }
+}
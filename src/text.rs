@@ -0,0 +1,129 @@
+//! Measuring text the way terminals lay it out.
+//!
+//! Text is taken apart into extended grapheme clusters: what reads as one character, even when
+//! it is made up of several `char`s, such as a letter with combining marks, an emoji ZWJ
+//! sequence, a flag or a Hangul syllable spelled out in jamo. Each cluster takes up one cell, or
+//! two if it is wide, and the cursor helpers never stop inside of one.
+//!
+//! ```
+//! use rustbox::text::{next_boundary, prev_boundary, str_width};
+//!
+//! // A family emoji, three emoji joined by zero-width joiners.
+//! let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+//! assert_eq!(str_width(family), 2);
+//! assert_eq!(next_boundary(family, 0), family.len());
+//!
+//! // The flag of Japan, a pair of regional indicators.
+//! let flags = "\u{1f1ef}\u{1f1f5}\u{1f1ef}\u{1f1f5}";
+//! assert_eq!(str_width(flags), 4);
+//! assert_eq!(next_boundary(flags, 0), 8);
+//! assert_eq!(prev_boundary(flags, 12), 8);
+//!
+//! // 각, as a leading consonant, a vowel and a trailing consonant.
+//! let jamo = "\u{1100}\u{1161}\u{11a8}x";
+//! assert_eq!(str_width(jamo), 3);
+//! assert_eq!(next_boundary(jamo, 0), 9);
+//! ```
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The number of columns `ch` takes up on its own: 2 for wide characters such as CJK and most
+/// emoji, 0 for combining marks and other zero-width characters, and 1 for everything else,
+/// including control characters.
+pub fn char_width(ch: char) -> usize {
+    ch.width().unwrap_or(1)
+}
+
+/// The number of columns the grapheme cluster `g` takes up.
+///
+/// This is 0 only for zero-width characters with nothing to attach to, as at the start of a
+/// string.
+pub fn grapheme_width(g: &str) -> usize {
+    g.width()
+}
+
+/// The number of columns `s` takes up when printed.
+///
+/// ```
+/// use rustbox::str_width;
+///
+/// assert_eq!(str_width("abc"), 3);
+/// assert_eq!(str_width("日本語"), 6);
+/// // An e followed by a combining acute accent.
+/// assert_eq!(str_width("e\u{301}"), 1);
+/// ```
+pub fn str_width(s: &str) -> usize {
+    graphemes(s).map(grapheme_width).sum()
+}
+
+/// The byte index of the first grapheme cluster boundary in `s` after byte `i`, or `s.len()`.
+pub fn next_boundary(s: &str, i: usize) -> usize {
+    s.grapheme_indices(true)
+        .map(|(start, _)| start)
+        .find(|&start| start > i)
+        .unwrap_or(s.len())
+}
+
+/// The byte index of the last grapheme cluster boundary in `s` before byte `i`, or 0.
+pub fn prev_boundary(s: &str, i: usize) -> usize {
+    s.grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|&start| start < i)
+        .last()
+        .unwrap_or(0)
+}
+
+/// The column of byte `i` of `s`, counting from where `s` is printed. An index inside a
+/// grapheme cluster gives the column of the cluster.
+///
+/// ```
+/// use rustbox::text::column_at;
+///
+/// assert_eq!(column_at("日本語", 3), 2);
+/// assert_eq!(column_at("e\u{301}x", 2), 0);
+/// assert_eq!(column_at("e\u{301}x", 3), 1);
+/// ```
+pub fn column_at(s: &str, i: usize) -> usize {
+    s.grapheme_indices(true)
+        .take_while(|&(start, g)| start + g.len() <= i)
+        .map(|(_, g)| grapheme_width(g))
+        .sum()
+}
+
+/// The byte index of the grapheme cluster of `s` that covers `column`, or `s.len()` if the
+/// text ends before it. The inverse of `column_at`, for instance to find what was clicked.
+///
+/// ```
+/// use rustbox::text::index_at;
+///
+/// assert_eq!(index_at("日本語", 3), 3);
+/// assert_eq!(index_at("日本語", 6), 9);
+/// ```
+pub fn index_at(s: &str, column: usize) -> usize {
+    let mut left = 0;
+    for (start, g) in s.grapheme_indices(true) {
+        left += grapheme_width(g);
+        if left > column {
+            return start;
+        }
+    }
+    s.len()
+}
+
+/// The extended grapheme clusters of `s`.
+pub(crate) fn graphemes(s: &str) -> ::unicode_segmentation::Graphemes<'_> {
+    s.graphemes(true)
+}
+
+/// The width of a cell holding `ch` followed by the rest of its grapheme cluster.
+#[cfg(feature = "pure")]
+pub(crate) fn cell_width(ch: char, rest: &str) -> usize {
+    if rest.is_empty() {
+        return char_width(ch);
+    }
+    let mut cluster = String::with_capacity(ch.len_utf8() + rest.len());
+    cluster.push(ch);
+    cluster.push_str(rest);
+    grapheme_width(&cluster)
+}
@@ -0,0 +1,73 @@
+extern crate rustbox;
+
+use rustbox::{Color, HeadlessBackend, RustBox, RB_NORMAL};
+
+const FAMILY: &str = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+const FLAGS: &str = "\u{1f1ef}\u{1f1f5}\u{1f1fa}\u{1f1f8}";
+// 각, as a leading consonant, a vowel and a trailing consonant.
+const JAMO: &str = "\u{1100}\u{1161}\u{11a8}";
+
+// The text of every cell of the first row, cluster and all.
+fn row(rb: &RustBox<HeadlessBackend>) -> Vec<String> {
+    (0..rb.width())
+        .map(|x| {
+            let (cell, rest) = rb.get_cluster(x, 0).unwrap();
+            format!("{}{}", cell.ch, rest)
+        })
+        .collect()
+}
+
+fn print(width: usize, s: &str) -> Vec<String> {
+    let rb = RustBox::headless(width, 1);
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, s);
+    row(&rb)
+}
+
+#[test]
+fn zwj_sequence() {
+    assert_eq!(print(4, &format!("{}x", FAMILY)), [FAMILY, " ", "x", " "]);
+}
+
+#[test]
+fn flags() {
+    assert_eq!(print(5, FLAGS), ["\u{1f1ef}\u{1f1f5}", " ", "\u{1f1fa}\u{1f1f8}", " ", " "]);
+}
+
+#[test]
+fn hangul_jamo() {
+    assert_eq!(print(3, &format!("{}x", JAMO)), [JAMO, " ", "x"]);
+}
+
+#[test]
+fn combining_marks() {
+    assert_eq!(print(3, "a\u{301}b"), ["a\u{301}", "b", " "]);
+    // With nothing to attach to, a leading mark is left out.
+    assert_eq!(print(3, "\u{301}ab"), ["a", "b", " "]);
+}
+
+#[test]
+fn wide_cluster_at_the_edge() {
+    assert_eq!(print(3, &format!("ab{}", FAMILY)), ["a", "b", " "]);
+
+    let rb = RustBox::headless(5, 1);
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "vwxyz");
+    rb.region(0, 0, 3, 1).print(1, 0, RB_NORMAL, Color::Default, Color::Default, &format!("a{}", FLAGS));
+    assert_eq!(row(&rb), ["v", "a", " ", "y", "z"]);
+}
+
+#[test]
+fn overwriting_a_cluster() {
+    let rb = RustBox::headless(2, 1);
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "e\u{301}");
+    rb.print_char(0, 0, RB_NORMAL, Color::Default, Color::Default, 'x');
+    assert_eq!(row(&rb), ["x", " "]);
+}
+
+#[test]
+fn clusters_survive_suspend() {
+    let rb = RustBox::headless(4, 1);
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, &format!("{}e\u{301}", FAMILY));
+    rb.present();
+    rb.suspend(|| ()).unwrap();
+    assert_eq!(row(&rb), [FAMILY, " ", "e\u{301}", " "]);
+}
@@ -0,0 +1,42 @@
+extern crate libc;
+extern crate rustbox;
+
+use rustbox::{Event, HeadlessBackend, InitOptions, Key, RustBox, MOD_NONE};
+
+fn ctrl_z_with(job_control: bool) -> RustBox<HeadlessBackend> {
+    let opts = InitOptions { job_control, ..Default::default() };
+    let rb = RustBox::with_backend(HeadlessBackend::new(10, 1), opts).unwrap();
+    rb.backend().push_input(Event::KeyEvent(Key::Ctrl('z'), MOD_NONE));
+    rb
+}
+
+// One test, so that no other test is running on another thread when forking.
+#[test]
+fn ctrl_z() {
+    match ctrl_z_with(false).poll_event(false) {
+        Ok(Event::KeyEvent(Key::Ctrl('z'), _)) => (),
+        ev => panic!("expected Ctrl-Z, got {:?}", ev),
+    }
+
+    // The process really is stopped, so this happens in a child that gets continued again.
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            // In a process group of its own, which isn't orphaned, so the stop isn't discarded.
+            libc::setpgid(0, 0);
+            let rb = ctrl_z_with(true);
+            let resized = match rb.poll_event(false) {
+                Ok(Event::ResizeEvent(10, 1)) => true,
+                _ => false,
+            };
+            libc::_exit(if resized { 0 } else { 1 });
+        }
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, libc::WUNTRACED), pid);
+        assert!(libc::WIFSTOPPED(status), "not stopped: {:x}", status);
+        libc::kill(pid, libc::SIGCONT);
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0, "status: {:x}", status);
+    }
+}
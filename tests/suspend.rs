@@ -0,0 +1,91 @@
+extern crate rustbox;
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use rustbox::{Backend, Cell, Color, EventResult, HeadlessBackend, InitError, InitOptions};
+use rustbox::{InputMode, OutputMode, RustBox, RB_NORMAL};
+
+// A headless backend with a screen that only changes on `present`, logging what is done to it.
+struct Screen {
+    back: HeadlessBackend,
+    shown: Mutex<Vec<char>>,
+    log: Mutex<Vec<String>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap()
+}
+
+impl Screen {
+    fn new(width: usize) -> Screen {
+        Screen {
+            back: HeadlessBackend::new(width, 1),
+            shown: Mutex::new(vec![' '; width]),
+            log: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn log(&self, what: String) {
+        lock(&self.log).push(what);
+    }
+}
+
+impl Backend for Screen {
+    fn width(&self) -> usize { self.back.width() }
+    fn height(&self) -> usize { self.back.height() }
+    fn clear(&self) { self.back.clear() }
+    fn set_cursor(&self, x: isize, y: isize) { self.back.set_cursor(x, y) }
+    fn get_cell(&self, x: usize, y: usize) -> Option<Cell> { self.back.get_cell(x, y) }
+    fn poll_event(&self, raw: bool) -> EventResult { self.back.poll_event(raw) }
+    fn peek_event(&self, timeout: Duration, raw: bool) -> EventResult { self.back.peek_event(timeout, raw) }
+    fn set_input_mode(&self, mode: InputMode) { self.back.set_input_mode(mode) }
+    fn set_output_mode(&self, mode: OutputMode) { self.back.set_output_mode(mode) }
+    fn wake(&self) { self.back.wake() }
+
+    fn present(&self) {
+        *lock(&self.shown) = (0..self.width()).map(|x| self.back.get_cell(x, 0).unwrap().ch).collect();
+        self.log("present".to_owned());
+    }
+
+    fn set_cell(&self, x: usize, y: usize, cell: Cell) {
+        self.set_cluster(x, y, cell, "");
+    }
+
+    fn set_cluster(&self, x: usize, y: usize, cell: Cell, rest: &str) {
+        self.back.set_cluster(x, y, cell, rest);
+        self.log(format!("set {}", cell.ch));
+    }
+
+    fn get_presented(&self, x: usize, y: usize) -> Option<(Cell, String)> {
+        self.back.get_cell(x, y).map(|cell| (Cell { ch: lock(&self.shown)[x], ..cell }, String::new()))
+    }
+
+    fn suspend(&self) {
+        self.log("suspend".to_owned());
+    }
+
+    fn resume(&self) -> Result<(), InitError> {
+        self.log("resume".to_owned());
+        Ok(())
+    }
+}
+
+#[test]
+fn redraws_presented_then_drawn() {
+    let rb = RustBox::with_backend(Screen::new(2), InitOptions::default()).unwrap();
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "ab");
+    rb.present();
+    rb.print(0, 0, RB_NORMAL, Color::Default, Color::Default, "cd");
+    lock(&rb.backend().log).clear();
+
+    rb.suspend(|| rb.backend().log("stopped".to_owned())).unwrap();
+
+    assert_eq!(*lock(&rb.backend().log), [
+        "suspend", "stopped", "resume",
+        "set a", "set b", "present",
+        "set c", "set d",
+    ]);
+    assert_eq!(*lock(&rb.backend().shown), ['a', 'b']);
+    assert_eq!(rb.get_cell(0, 0).unwrap().ch, 'c');
+}
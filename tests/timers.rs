@@ -0,0 +1,78 @@
+extern crate rustbox;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustbox::{Event, EventResult, RustBox};
+
+fn timer(ev: EventResult) -> usize {
+    match ev {
+        Ok(Event::Timer(token)) => token,
+        ev => panic!("expected a timer, got {:?}", ev),
+    }
+}
+
+fn nothing(ev: EventResult) {
+    match ev {
+        Ok(Event::NoEvent) => (),
+        ev => panic!("expected no event, got {:?}", ev),
+    }
+}
+
+#[test]
+fn one_shot() {
+    let rb = RustBox::headless(10, 1);
+    let start = Instant::now();
+    rb.set_timer(1, Duration::from_millis(20));
+    assert_eq!(timer(rb.poll_event(false)), 1);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    nothing(rb.peek_event(Duration::from_millis(40), false));
+}
+
+#[test]
+fn repeating() {
+    let rb = RustBox::headless(10, 1);
+    rb.set_repeating_timer(2, Duration::from_millis(10));
+    for _ in 0..3 {
+        assert_eq!(timer(rb.poll_event(false)), 2);
+    }
+    rb.cancel_timer(2);
+    nothing(rb.peek_event(Duration::from_millis(30), false));
+}
+
+#[test]
+fn cancel() {
+    let rb = RustBox::headless(10, 1);
+    rb.set_timer(3, Duration::from_millis(10));
+    rb.set_timer(4, Duration::from_millis(20));
+    rb.cancel_timer(3);
+    assert_eq!(timer(rb.poll_event(false)), 4);
+    rb.cancel_timer(5);
+    nothing(rb.peek_event(Duration::from_millis(30), false));
+}
+
+#[test]
+fn ordering() {
+    let rb = RustBox::headless(10, 1);
+    rb.set_timer(1, Duration::from_millis(30));
+    rb.set_timer(2, Duration::from_millis(10));
+    rb.set_timer(3, Duration::from_millis(20));
+    // Setting a timer again replaces it.
+    rb.set_timer(2, Duration::from_millis(40));
+    let order: Vec<usize> = (0..3).map(|_| timer(rb.poll_event(false))).collect();
+    assert_eq!(order, [3, 1, 2]);
+}
+
+#[test]
+fn set_from_another_thread() {
+    let rb = RustBox::headless(10, 1);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(20));
+            rb.set_timer(6, Duration::from_millis(10));
+        });
+        let start = Instant::now();
+        assert_eq!(timer(rb.poll_event(false)), 6);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    });
+}